@@ -0,0 +1,400 @@
+//! Minimal CSS parser used for sanitization.
+//!
+//! It does not try to understand values. It only splits input into declarations in a way which
+//! is robust against strings, comments and nested parentheses.
+
+/// Rule of a stylesheet as it is written in a source.
+pub(crate) enum Rule {
+    /// `prelude { block }`, e.g. a style rule with selectors
+    Qualified { prelude: String, block: String },
+    /// `@name prelude;` or `@name prelude { block }`
+    At {
+        name: String,
+        prelude: String,
+        block: Option<String>,
+    },
+}
+
+/// Declaration as it is written in a source.
+pub(crate) struct Declaration {
+    pub(crate) name: String,
+    pub(crate) value: String,
+    pub(crate) important: bool,
+}
+
+/// Replaces comments with spaces. Comment markers inside strings are kept as is.
+pub(crate) fn strip_comments(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                result.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                result.push(c);
+            }
+            None if c == '/' && chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                result.push(' ');
+            }
+            None => result.push(c),
+        }
+    }
+    result
+}
+
+/// Splits input by a delimiter which is not inside of a string, parentheses or brackets.
+pub(crate) fn split_top_level(input: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (index, c) in input.char_indices() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = depth.saturating_sub(1),
+                c if c == delimiter && depth == 0 => {
+                    parts.push(&input[start..index]);
+                    start = index + c.len_utf8();
+                }
+                _ => {}
+            },
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn strip_important(value: &str) -> (&str, bool) {
+    if let Some(index) = value.rfind('!') {
+        let flag = value[index + 1..].trim();
+        if flag.eq_ignore_ascii_case("important") {
+            return (value[..index].trim_end(), true);
+        }
+    }
+    (value, false)
+}
+
+/// Parses a list of declarations, e.g. content of a `style` attribute.
+/// Comments are removed, empty declarations are skipped.
+pub(crate) fn parse_declarations(input: &str) -> Vec<Declaration> {
+    let input = strip_comments(input);
+    split_top_level(&input, ';')
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let (name, value) = part.split_once(':').unwrap_or((part, ""));
+            let (value, important) = strip_important(value.trim());
+            Declaration {
+                name: name.trim().to_ascii_lowercase(),
+                value: value.to_owned(),
+                important,
+            }
+        })
+        .collect()
+}
+
+/// Finds the first of `targets` which is not inside of a string, parentheses or brackets.
+fn find_top_level(input: &str, targets: &[char]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if depth == 0 && targets.contains(&c) => return Some(index),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// Finds length of a block which starts right after an opening brace.
+fn block_len(input: &str) -> usize {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') if depth == 0 => return index,
+            (None, '}') => depth -= 1,
+            (None, _) => {}
+        }
+    }
+    input.len()
+}
+
+/// Parses a list of rules, e.g. content of a `style` element or a block of `@media`.
+/// Input should not contain comments.
+pub(crate) fn parse_rules(input: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start();
+        // HTML comment markers are allowed between rules and ignored
+        if let Some(r) = rest
+            .strip_prefix("<!--")
+            .or_else(|| rest.strip_prefix("-->"))
+        {
+            rest = r;
+            continue;
+        }
+        if rest.is_empty() {
+            break;
+        }
+
+        let (name, body) = match rest.strip_prefix('@') {
+            Some(at_rule) => {
+                let name_len = at_rule
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                    .unwrap_or(at_rule.len());
+                (
+                    Some(at_rule[..name_len].to_ascii_lowercase()),
+                    &at_rule[name_len..],
+                )
+            }
+            None => (None, rest),
+        };
+
+        let delimiters: &[char] = if name.is_some() { &[';', '{'] } else { &['{'] };
+        let Some(prelude_len) = find_top_level(body, delimiters) else {
+            // unterminated rule is dropped
+            break;
+        };
+        let prelude = body[..prelude_len].trim().to_owned();
+        let after_prelude = &body[prelude_len + 1..];
+
+        let block = if body[prelude_len..].starts_with('{') {
+            let len = block_len(after_prelude);
+            rest = after_prelude.get(len + 1..).unwrap_or("");
+            Some(after_prelude[..len].to_owned())
+        } else {
+            rest = after_prelude;
+            None
+        };
+
+        rules.push(match name {
+            Some(name) => Rule::At {
+                name,
+                prelude,
+                block,
+            },
+            None => Rule::Qualified {
+                prelude,
+                block: block.unwrap_or_default(),
+            },
+        });
+    }
+    rules
+}
+
+/// Checks that a property name is a plain identifier.
+pub(crate) fn is_valid_property_name(name: &str) -> bool {
+    let name = name.strip_prefix('-').unwrap_or(name);
+    let mut chars = name.chars();
+    matches!(chars.next(), Some('a'..='z'))
+        && chars.all(|c| matches!(c, 'a'..='z' | '0'..='9' | '-'))
+}
+
+/// Checks that quotes and parentheses of a value are balanced.
+pub(crate) fn is_balanced(value: &str) -> bool {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    for c in value.chars() {
+        match quote {
+            Some(_) if c == '\n' || c == '\r' || c == '\x0C' => return false,
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' => depth += 1,
+                ')' => match depth.checked_sub(1) {
+                    Some(d) => depth = d,
+                    None => return false,
+                },
+                _ => {}
+            },
+        }
+    }
+    quote.is_none() && depth == 0
+}
+
+/// Finds length of an argument list which starts right after an opening parenthesis.
+fn arguments_len(input: &str) -> usize {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => return index,
+            (None, ')') => depth -= 1,
+            (None, _) => {}
+        }
+    }
+    input.len()
+}
+
+/// Finds functions used in a value together with their arguments,
+/// e.g. `url` and `rgb` in `url(a.png) rgb(0,0,0)`. Names are lowercased.
+pub(crate) fn functions(value: &str) -> Vec<(String, &str)> {
+    let mut result = Vec::new();
+    let mut quote: Option<char> = None;
+    for (index, c) in value.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => {
+                let name_start = value[..index]
+                    .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                let name = value[name_start..index].to_ascii_lowercase();
+                let arguments = &value[index + 1..];
+                result.push((name, &arguments[..arguments_len(arguments)]));
+            }
+            (None, _) => {}
+        }
+    }
+    result
+}
+
+/// Finds contents of quoted strings which are not nested in parentheses,
+/// e.g. `a.png` in `"a.png" 1x, url(b.png) 2x`.
+pub(crate) fn strings(input: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<(char, usize)> = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some((q, start)), c) if c == q => {
+                if depth == 0 {
+                    result.push(&input[start..index]);
+                }
+                quote = None;
+            }
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some((c, index + 1)),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, _) => {}
+        }
+    }
+    result
+}
+
+/// Removes quotes and whitespace around an argument of `url()`.
+pub(crate) fn unquote(argument: &str) -> &str {
+    let argument = argument.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = argument
+            .strip_prefix(quote)
+            .and_then(|a| a.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    argument
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarations() {
+        let declarations =
+            parse_declarations("color: red; /* ; */ content: 'a;b' ; ; margin:0 ! IMPORTANT");
+        let parsed: Vec<(&str, &str, bool)> = declarations
+            .iter()
+            .map(|d| (d.name.as_str(), d.value.as_str(), d.important))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("color", "red", false),
+                ("content", "'a;b'", false),
+                ("margin", "0", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let rules = parse_rules(
+            "<!-- a > b { color: red } @import url('x;y.css'); @media screen { p { margin: 0 } } -->",
+        );
+        let parsed: Vec<String> = rules
+            .iter()
+            .map(|rule| match rule {
+                Rule::Qualified { prelude, block } => format!("{}|{}", prelude, block),
+                Rule::At {
+                    name,
+                    prelude,
+                    block,
+                } => format!("@{}|{}|{:?}", name, prelude, block),
+            })
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                "a > b| color: red ",
+                "@import|url('x;y.css')|None",
+                "@media|screen|Some(\" p { margin: 0 } \")",
+            ]
+        );
+    }
+
+    #[test]
+    fn function_names() {
+        let names: Vec<(String, &str)> = functions("url( 'a(b).png' ) RGB(0, calc(1 + 2), 0)");
+        assert_eq!(
+            names,
+            vec![
+                ("url".to_owned(), " 'a(b).png' "),
+                ("rgb".to_owned(), "0, calc(1 + 2), 0"),
+                ("calc".to_owned(), "1 + 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn top_level_strings() {
+        assert_eq!(
+            strings("\"a.png\" 1x, url('b.png') 2x, 'c(.png' type(\"image/png\")"),
+            vec!["a.png", "c(.png"]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Parsed documents, which can be inspected and changed before and after sanitization
+//! without parsing them again.
+//!
+//! # Examples
+//!
+//! ```
+//! use sanitize_html::dom::{Document, Node, NodeKind};
+//! use sanitize_html::rules::predefined::BASIC;
+//! use sanitize_html::{sanitize_dom, serialize};
+//!
+//! let document = Document::parse("<p>Hello <blink>big</blink> <b>world</b></p>");
+//! let sanitized = sanitize_dom(&BASIC, &document).unwrap();
+//!
+//! let p = sanitized.root().children()[0].clone();
+//! assert_eq!(p.kind(), NodeKind::Element);
+//! assert_eq!(p.name(), Some("p"));
+//!
+//! let link = Node::new_element("a");
+//! link.set_attribute("href", "/more");
+//! link.append(&Node::new_text("more"));
+//! p.append(&link);
+//! assert_eq!(
+//!     serialize(&sanitized).unwrap(),
+//!     "<p>Hello big <b>world</b><a href=\"/more\">more</a></p>"
+//! );
+//! ```
+
+use crate::parse;
+use crate::rcdom::{self, Handle, NodeData, append};
+use html5ever::{Attribute, LocalName, QualName, ns};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+/// Kind of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// Root of a document.
+    Document,
+    /// Document type declaration.
+    Doctype,
+    /// Text.
+    Text,
+    /// Comment.
+    Comment,
+    /// Element with attributes and children.
+    Element,
+    /// Processing instruction.
+    ProcessingInstruction,
+}
+
+/// Node of a document
+///
+/// It is a reference to a node of a tree, so clones refer to the same node and see changes
+/// made through each other. Nodes are compared by identity.
+#[derive(Clone)]
+pub struct Node(Handle);
+
+/// Name of an attribute with its prefix, e.g. `xlink:href`.
+fn attribute_name(name: &QualName) -> String {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local),
+        None => name.local.to_string(),
+    }
+}
+
+impl Node {
+    /// Creates an HTML element without attributes and children.
+    pub fn new_element(name: &str) -> Self {
+        Node(rcdom::Node::new(NodeData::Element {
+            name: QualName::new(None, ns!(html), LocalName::from(name)),
+            attrs: RefCell::new(Vec::new()),
+            template_contents: RefCell::new(None),
+            mathml_annotation_xml_integration_point: false,
+        }))
+    }
+
+    /// Creates a text node.
+    pub fn new_text(text: &str) -> Self {
+        Node(rcdom::Node::new(NodeData::Text {
+            contents: RefCell::new(text.into()),
+        }))
+    }
+
+    /// Creates a comment.
+    pub fn new_comment(text: &str) -> Self {
+        Node(rcdom::Node::new(NodeData::Comment {
+            contents: text.into(),
+        }))
+    }
+
+    /// Returns a kind of the node.
+    pub fn kind(&self) -> NodeKind {
+        match self.0.data {
+            NodeData::Document => NodeKind::Document,
+            NodeData::Doctype { .. } => NodeKind::Doctype,
+            NodeData::Text { .. } => NodeKind::Text,
+            NodeData::Comment { .. } => NodeKind::Comment,
+            NodeData::Element { .. } => NodeKind::Element,
+            NodeData::ProcessingInstruction { .. } => NodeKind::ProcessingInstruction,
+        }
+    }
+
+    /// Returns a local name of an element, e.g. `p` or `svg`, or `None` for other nodes.
+    pub fn name(&self) -> Option<&str> {
+        match self.0.data {
+            NodeData::Element { ref name, .. } => Some(&name.local),
+            _ => None,
+        }
+    }
+
+    /// Returns a namespace URL of an element, e.g. `http://www.w3.org/2000/svg`,
+    /// or `None` for other nodes.
+    pub fn namespace(&self) -> Option<&str> {
+        match self.0.data {
+            NodeData::Element { ref name, .. } => Some(&name.ns),
+            _ => None,
+        }
+    }
+
+    /// Returns text of a text node or a comment.
+    pub fn text(&self) -> Option<String> {
+        match self.0.data {
+            NodeData::Text { ref contents } => Some(contents.borrow().to_string()),
+            NodeData::Comment { ref contents } => Some(contents.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Replaces text of a text node. Returns `false` for other nodes.
+    pub fn set_text(&self, text: &str) -> bool {
+        match self.0.data {
+            NodeData::Text { ref contents } => {
+                *contents.borrow_mut() = text.into();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns a value of an attribute of an element.
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        match self.0.data {
+            NodeData::Element { ref attrs, .. } => attrs
+                .borrow()
+                .iter()
+                .find(|attr| attribute_name(&attr.name) == name)
+                .map(|attr| attr.value.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Returns names and values of attributes of an element in document order.
+    pub fn attributes(&self) -> Vec<(String, String)> {
+        match self.0.data {
+            NodeData::Element { ref attrs, .. } => attrs
+                .borrow()
+                .iter()
+                .map(|attr| (attribute_name(&attr.name), attr.value.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sets a value of an attribute of an element, adding the attribute if it is missing.
+    /// Returns `false` for other nodes.
+    pub fn set_attribute(&self, name: &str, value: &str) -> bool {
+        let NodeData::Element { ref attrs, .. } = self.0.data else {
+            return false;
+        };
+        let mut attrs = attrs.borrow_mut();
+        match attrs
+            .iter_mut()
+            .find(|attr| attribute_name(&attr.name) == name)
+        {
+            Some(attr) => attr.value = value.into(),
+            None => attrs.push(Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(name)),
+                value: value.into(),
+            }),
+        }
+        true
+    }
+
+    /// Removes an attribute of an element and returns its value.
+    pub fn remove_attribute(&self, name: &str) -> Option<String> {
+        let NodeData::Element { ref attrs, .. } = self.0.data else {
+            return None;
+        };
+        let mut attrs = attrs.borrow_mut();
+        let index = attrs
+            .iter()
+            .position(|attr| attribute_name(&attr.name) == name)?;
+        Some(attrs.remove(index).value.to_string())
+    }
+
+    /// Returns a parent of the node, if any.
+    pub fn parent(&self) -> Option<Node> {
+        self.0.parent_node().map(Node)
+    }
+
+    /// Returns children of the node.
+    pub fn children(&self) -> Vec<Node> {
+        self.0.children.borrow().iter().cloned().map(Node).collect()
+    }
+
+    /// Moves a node to the end of children of this node, removing it from its parent first.
+    ///
+    /// Returns `false` and changes nothing if this node can have no children, if `child` is
+    /// a document, or if `child` is this node or one of its ancestors.
+    pub fn append(&self, child: &Node) -> bool {
+        if !matches!(self.kind(), NodeKind::Document | NodeKind::Element)
+            || child.kind() == NodeKind::Document
+        {
+            return false;
+        }
+        let mut ancestor = Some(self.clone());
+        while let Some(node) = ancestor {
+            if node == *child {
+                return false;
+            }
+            ancestor = node.parent();
+        }
+        child.detach();
+        append(&self.0, child.0.clone());
+        true
+    }
+
+    /// Removes the node from its parent.
+    pub fn detach(&self) {
+        rcdom::remove_from_parent(&self.0);
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Node {}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Node");
+        debug.field("kind", &self.kind());
+        if let Some(name) = self.name() {
+            debug.field("name", &name);
+        }
+        if let Some(text) = self.text() {
+            debug.field("text", &text);
+        }
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// Parsed HTML document or fragment
+pub struct Document {
+    root: Handle,
+    context: Option<String>,
+}
+
+impl Document {
+    pub(crate) fn new(root: Handle, context: Option<String>) -> Self {
+        Document { root, context }
+    }
+
+    /// Parses a document.
+    pub fn parse(input: &str) -> Self {
+        Self::parse_bytes(input.as_bytes())
+    }
+
+    /// Parses a document from UTF-8 bytes.
+    pub fn parse_bytes(input: &[u8]) -> Self {
+        Document::new(parse::parse_dom(input).document, None)
+    }
+
+    /// Parses a document from bytes in a given encoding. A byte order mark overrides it.
+    ///
+    /// Use [`sniff`](crate::encoding::sniff) to detect an encoding.
+    #[cfg(feature = "encoding")]
+    pub fn parse_bytes_encoded(input: &[u8], encoding: &'static crate::encoding::Encoding) -> Self {
+        Document::new(parse::parse_dom_encoded(input, encoding).document, None)
+    }
+
+    /// Parses a fragment which will be inserted into a `context` element.
+    pub fn parse_fragment(context: &str, input: &str) -> Self {
+        Self::parse_fragment_bytes(context, input.as_bytes())
+    }
+
+    /// Parses a fragment from UTF-8 bytes.
+    pub fn parse_fragment_bytes(context: &str, input: &[u8]) -> Self {
+        let dom = parse::parse_fragment_dom(input, context);
+        let root = rcdom::Node::new(NodeData::Document);
+        // html5ever places a parsed fragment under a synthetic `html` root element
+        if let Some(html) = dom.document.children.borrow().first() {
+            for child in html.children.take() {
+                child.parent.set(None);
+                append(&root, child);
+            }
+        }
+        Document::new(root, Some(context.to_owned()))
+    }
+
+    /// Returns the root node. Its kind is [`NodeKind::Document`], content of a document
+    /// is its children.
+    pub fn root(&self) -> Node {
+        Node(self.root.clone())
+    }
+
+    /// Returns a name of an element a fragment is parsed for, or `None` for a whole document.
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    pub(crate) fn handle(&self) -> &Handle {
+        &self.root
+    }
+
+    pub(crate) fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        match self.context {
+            Some(ref context) => parse::unparse_fragment(&self.root, context),
+            None => parse::unparse_document(&self.root),
+        }
+    }
+}
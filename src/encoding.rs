@@ -0,0 +1,320 @@
+//! Decoding of input and encoding of output in legacy encodings.
+//!
+//! Available with the `encoding` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use sanitize_html::encoding::{Encoding, EncodingOptions};
+//! use sanitize_html::rules::predefined::BASIC;
+//! use sanitize_html::sanitize_encoded;
+//!
+//! let input = b"<meta charset=\"iso-8859-1\"><p>Caf\xe9</p>";
+//! let output = sanitize_encoded(&BASIC, input, &EncodingOptions::new()).unwrap();
+//! assert_eq!(output, "<p>Café</p>".as_bytes());
+//!
+//! let options = EncodingOptions::new().output(Encoding::for_label(b"latin1").unwrap());
+//! let output = sanitize_encoded(&BASIC, input, &options).unwrap();
+//! assert_eq!(output, b"<p>Caf\xe9</p>");
+//! ```
+
+pub use encoding_rs::Encoding;
+
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252, X_USER_DEFINED};
+
+/// Number of bytes which are searched for a `meta` element declaring an encoding.
+const PRESCAN_LEN: usize = 1024;
+
+/// Encodings of an input and of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingOptions {
+    /// Encoding of an input. If it is not set, it is detected by [`sniff`].
+    pub input: Option<&'static Encoding>,
+    /// Encoding of an output. Characters which it can not represent are written as
+    /// numeric character references.
+    pub output: &'static Encoding,
+}
+
+impl Default for EncodingOptions {
+    fn default() -> Self {
+        EncodingOptions {
+            input: None,
+            output: UTF_8,
+        }
+    }
+}
+
+impl EncodingOptions {
+    /// Creates options which detect an input encoding and produce UTF-8.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets encoding of an input
+    pub fn input(mut self, encoding: &'static Encoding) -> Self {
+        self.input = Some(encoding);
+        self
+    }
+
+    /// Sets encoding of an input by a label, e.g. a `charset` parameter of a `Content-Type`
+    /// header. Unknown labels are ignored, so the encoding is detected.
+    pub fn input_label(mut self, label: &str) -> Self {
+        self.input = Encoding::for_label(label.as_bytes()).or(self.input);
+        self
+    }
+
+    /// Sets encoding of an output
+    pub fn output(mut self, encoding: &'static Encoding) -> Self {
+        self.output = encoding;
+        self
+    }
+}
+
+/// Detects encoding of a document the way a browser does without a transport layer hint.
+///
+/// A byte order mark wins, then an encoding declared by a `meta` element in the first
+/// 1024 bytes. Otherwise input is UTF-8 if it is valid UTF-8 and windows-1252 if it is not.
+///
+/// # Examples
+///
+/// ```
+/// use sanitize_html::encoding::sniff;
+///
+/// assert_eq!(sniff(b"<meta charset=shift_jis>").name(), "Shift_JIS");
+/// assert_eq!(sniff("<p>Café</p>".as_bytes()).name(), "UTF-8");
+/// assert_eq!(sniff(b"<p>Caf\xe9</p>").name(), "windows-1252");
+/// ```
+pub fn sniff(input: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(input) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&input[..input.len().min(PRESCAN_LEN)]) {
+        return encoding;
+    }
+    match std::str::from_utf8(input) {
+        Ok(_) => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+pub(crate) fn encode(output: &[u8], encoding: &'static Encoding) -> Vec<u8> {
+    if encoding.output_encoding() == UTF_8 {
+        return output.to_vec();
+    }
+    let output = String::from_utf8_lossy(output);
+    let (bytes, _, _) = encoding.encode(&output);
+    bytes.into_owned()
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn starts_with_ignore_case(input: &[u8], prefix: &[u8]) -> bool {
+    input.len() >= prefix.len() && input[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn find(input: &[u8], needle: &[u8]) -> Option<usize> {
+    input
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Searches for a `meta` element which declares an encoding.
+fn prescan(input: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    while position < input.len() {
+        let rest = &input[position..];
+        if rest.starts_with(b"<!--") {
+            position += find(rest, b"-->").map_or(rest.len(), |end| end + 3);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest.get(5).is_some_and(|&c| is_space(c) || c == b'/')
+        {
+            let mut attributes = Attributes(&rest[5..]);
+            if let Some(encoding) = meta_encoding(&mut attributes) {
+                return Some(encoding);
+            }
+            position = input.len() - attributes.0.len();
+        } else if let Some(tag) = rest
+            .strip_prefix(b"<")
+            .map(|tag| tag.strip_prefix(b"/").unwrap_or(tag))
+            && tag.first().is_some_and(u8::is_ascii_alphabetic)
+        {
+            // attributes of other tags are skipped, so values can not declare an encoding
+            let name_len = tag
+                .iter()
+                .take_while(|&&c| !is_space(c) && c != b'>')
+                .count();
+            let mut attributes = Attributes(&tag[name_len..]);
+            attributes.by_ref().for_each(drop);
+            position = input.len() - attributes.0.len() + 1;
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += find(rest, b">").map_or(rest.len(), |end| end + 1);
+        } else {
+            position += 1;
+        }
+    }
+    None
+}
+
+/// Attributes of a tag, which are read up to the end of the tag.
+struct Attributes<'a>(&'a [u8]);
+
+impl Attributes<'_> {
+    fn skip_over(&mut self, predicate: impl Fn(u8) -> bool) {
+        let len = self.0.iter().take_while(|&&c| predicate(c)).count();
+        self.0 = &self.0[len..];
+    }
+
+    fn take_until(&mut self, predicate: impl Fn(u8) -> bool) -> Vec<u8> {
+        let len = self.0.iter().take_while(|&&c| !predicate(c)).count();
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        value.to_ascii_lowercase()
+    }
+}
+
+impl Iterator for Attributes<'_> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_over(|c| is_space(c) || c == b'/');
+        if self.0.first().is_none_or(|&c| c == b'>') {
+            return None;
+        }
+        let name = self.take_until(|c| is_space(c) || matches!(c, b'=' | b'/' | b'>'));
+        self.skip_over(is_space);
+        if self.0.first() != Some(&b'=') {
+            return Some((name, Vec::new()));
+        }
+        self.0 = &self.0[1..];
+        self.skip_over(is_space);
+        let value = match self.0.first() {
+            Some(&quote @ (b'"' | b'\'')) => {
+                self.0 = &self.0[1..];
+                let value = self.take_until(|c| c == quote);
+                self.0 = self.0.get(1..).unwrap_or_default();
+                value
+            }
+            _ => self.take_until(|c| is_space(c) || c == b'>'),
+        };
+        Some((name, value))
+    }
+}
+
+/// Reads attributes of a `meta` element and returns an encoding it declares.
+fn meta_encoding(attributes: &mut Attributes) -> Option<&'static Encoding> {
+    let mut charset = None;
+    let mut content = None;
+    let mut pragma = false;
+    for (name, value) in attributes {
+        match &name[..] {
+            b"charset" if charset.is_none() => charset = Some(value),
+            b"content" if content.is_none() => content = Some(value),
+            b"http-equiv" => pragma |= value == b"content-type",
+            _ => {}
+        }
+    }
+    let label = match (charset, content) {
+        (Some(charset), _) => charset,
+        (None, Some(content)) if pragma => charset_from_content(&content)?,
+        _ => return None,
+    };
+    let encoding = Encoding::for_label(&label)?;
+    Some(match encoding {
+        encoding if encoding == UTF_16BE || encoding == UTF_16LE => UTF_8,
+        encoding if encoding == X_USER_DEFINED => WINDOWS_1252,
+        encoding => encoding,
+    })
+}
+
+/// Extracts an encoding label from a `content` attribute, e.g. `text/html; charset=utf-8`.
+fn charset_from_content(content: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = content;
+    loop {
+        rest = &rest[find(rest, b"charset")? + 7..];
+        let mut value = Attributes(rest);
+        value.skip_over(is_space);
+        if value.0.first() != Some(&b'=') {
+            continue;
+        }
+        value.0 = &value.0[1..];
+        value.skip_over(is_space);
+        return match value.0.first() {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let label = &value.0[1..];
+                let end = label.iter().position(|&c| c == quote)?;
+                Some(label[..end].to_vec())
+            }
+            _ => Some(value.take_until(|c| is_space(c) || c == b';')),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(input: &[u8]) -> &'static str {
+        sniff(input).name()
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(name(b"\xEF\xBB\xBF<meta charset=latin1>"), "UTF-8");
+        assert_eq!(name(b"\xFF\xFEa\x00"), "UTF-16LE");
+        assert_eq!(name(b"\xFE\xFF\x00a"), "UTF-16BE");
+    }
+
+    #[test]
+    fn meta_charset() {
+        assert_eq!(name(b"<META CHARSET='KOI8-R'>"), "KOI8-R");
+        assert_eq!(name(b"<meta/charset=euc-jp>"), "EUC-JP");
+        assert_eq!(name(b"<meta charset=utf-16>\xe9"), "UTF-8");
+        assert_eq!(name(b"<meta charset=x-user-defined>"), "windows-1252");
+        assert_eq!(
+            name(b"<!-- <meta charset=koi8-r> --><meta charset=gbk>"),
+            "GBK"
+        );
+        assert_eq!(name(b"<metadata charset=koi8-r>"), "UTF-8");
+        assert_eq!(name(b"<meta charset=unknown><meta charset=gbk>"), "GBK");
+    }
+
+    #[test]
+    fn other_tags() {
+        assert_eq!(name(b"<a title=\"<meta charset=koi8-r>\">a</a>"), "UTF-8");
+        assert_eq!(
+            name(b"<p class='<meta charset=koi8-r>'></p><meta charset=gbk>"),
+            "GBK"
+        );
+        assert_eq!(name(b"</a title=\"<meta charset=koi8-r>\">"), "UTF-8");
+        assert_eq!(name(b"<?xml <meta charset=koi8-r>"), "UTF-8");
+        assert_eq!(name(b"<!doctype html><meta charset=gbk>"), "GBK");
+    }
+
+    #[test]
+    fn meta_content() {
+        let input =
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; Charset = 'iso-8859-2'\">";
+        assert_eq!(name(input), "ISO-8859-2");
+        let input = b"<meta content=\"text/html;charset=big5\" http-equiv=content-type>";
+        assert_eq!(name(input), "Big5");
+        // content is ignored without http-equiv
+        assert_eq!(name(b"<meta content=\"text/html; charset=big5\">"), "UTF-8");
+    }
+
+    #[test]
+    fn prescan_window() {
+        let mut input = vec![b' '; PRESCAN_LEN];
+        input.extend_from_slice(b"<meta charset=gbk>");
+        assert_eq!(name(&input), "UTF-8");
+    }
+
+    #[test]
+    fn options() {
+        let options = EncodingOptions::new().input_label(" latin1 ");
+        assert_eq!(options.input, Some(WINDOWS_1252));
+        assert_eq!(options.input_label("unknown").input, Some(WINDOWS_1252));
+        assert_eq!(EncodingOptions::new().input_label("unknown").input, None);
+    }
+}
@@ -1,20 +1,88 @@
 //! Errors, which can be emited by sanitization procedure.
 
+use crate::report::Violation;
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+/// Kind of a resource limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// Size of an input in bytes
+    InputSize,
+    /// Nesting depth of elements
+    Depth,
+    /// Number of nodes in a document
+    Nodes,
+    /// Number of attributes of a single element
+    Attributes,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::InputSize => write!(f, "input size"),
+            Limit::Depth => write!(f, "nesting depth"),
+            Limit::Nodes => write!(f, "node count"),
+            Limit::Attributes => write!(f, "attribute count"),
+        }
+    }
+}
 
 /// Sanitization error
 #[derive(Debug)]
-pub struct SanitizeError(pub(crate) Box<dyn Error>);
+#[non_exhaustive]
+pub enum SanitizeError {
+    /// Input could not be read or parsed.
+    Parse(io::Error),
+    /// Sanitized document could not be serialized.
+    Serialize(io::Error),
+    /// Sanitized document is not a valid UTF-8 string.
+    Utf8(FromUtf8Error),
+    /// Input exceeds a resource limit.
+    LimitExceeded {
+        /// Limit which was exceeded
+        limit: Limit,
+        /// Configured maximum
+        max: usize,
+    },
+    /// Input was rejected by strict sanitization rules.
+    Policy(Vec<Violation>),
+}
 
 impl fmt::Display for SanitizeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            SanitizeError::Parse(e) => write!(f, "failed to parse input: {}", e),
+            SanitizeError::Serialize(e) => write!(f, "failed to serialize document: {}", e),
+            SanitizeError::Utf8(e) => write!(f, "sanitized document is not valid UTF-8: {}", e),
+            SanitizeError::LimitExceeded { limit, max } => {
+                write!(f, "{} exceeds the limit of {}", limit, max)
+            }
+            SanitizeError::Policy(violations) => {
+                write!(f, "input rejected by sanitization rules")?;
+                if let Some(first) = violations.first() {
+                    write!(f, ": {}", first)?;
+                }
+                if violations.len() > 1 {
+                    write!(f, " (and {} more)", violations.len() - 1)?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
 impl Error for SanitizeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.0.source()
+        match self {
+            SanitizeError::Parse(e) => Some(e),
+            SanitizeError::Serialize(e) => Some(e),
+            SanitizeError::Utf8(e) => Some(e),
+            SanitizeError::LimitExceeded { .. } => None,
+            SanitizeError::Policy(_) => None,
+        }
     }
 }
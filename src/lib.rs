@@ -14,31 +14,257 @@
 
 #![deny(missing_docs)]
 
+mod css;
+pub mod dom;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 pub mod errors;
 mod parse;
 mod rcdom;
+pub mod report;
 pub mod rules;
 mod sanitize;
+mod stream;
 mod tests;
 
+use crate::dom::Document;
+use crate::errors::Limit;
 use crate::errors::SanitizeError;
+use crate::report::Report;
+use crate::report::{Change, ChangeKind};
 use crate::rules::Rules;
-use std::error::Error;
+use crate::rules::limits::OnLimit;
+use std::io::{Read, Write};
+
+/// Applies the input size limit. Input is cut at a character boundary, unless the rules say
+/// to fail. Strict mode always fails, because it never changes an input.
+fn limit_input<'i>(rules: &Rules, input: &'i [u8]) -> Result<&'i [u8], SanitizeError> {
+    let limits = &rules.limits;
+    match limits.max_input_bytes {
+        Some(max) if input.len() > max => {
+            if rules.strict || limits.on_exceed == OnLimit::Fail {
+                return Err(SanitizeError::LimitExceeded {
+                    limit: Limit::InputSize,
+                    max,
+                });
+            }
+            let mut end = max;
+            while end > 0 && input[end] & 0xC0 == 0x80 {
+                end -= 1;
+            }
+            Ok(&input[..end])
+        }
+        _ => Ok(input),
+    }
+}
+
+/// [`limit_input`] for a string.
+fn limit_input_str<'i>(rules: &Rules, input: &'i str) -> Result<&'i str, SanitizeError> {
+    let len = limit_input(rules, input.as_bytes())?.len();
+    Ok(&input[..len])
+}
+
+fn check_strict(report: Option<Report>) -> Result<(), SanitizeError> {
+    match report {
+        Some(report) if !report.is_empty() => Err(SanitizeError::Policy(report.changes)),
+        _ => Ok(()),
+    }
+}
+
+/// Sanitize a parsed document
+///
+/// In strict mode it fails if the document has anything to remove or change.
+pub fn sanitize_dom(rules: &Rules, document: &Document) -> Result<Document, SanitizeError> {
+    let (new_document, report) = sanitize::sanitize_document(document, rules, rules.strict)?;
+    check_strict(report)?;
+    Ok(new_document)
+}
+
+/// Serialize a document to HTML string
+pub fn serialize(document: &Document) -> Result<String, SanitizeError> {
+    let result_bytes = document.to_bytes().map_err(SanitizeError::Serialize)?;
+    let result_string = String::from_utf8(result_bytes).map_err(SanitizeError::Utf8)?;
+    Ok(result_string)
+}
 
 /// Sanitize HTML bytes
 pub fn sanitize_bytes(rules: &Rules, input: &[u8]) -> Result<Vec<u8>, SanitizeError> {
-    fn inner(rules: &Rules, input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let dom = parse::parse_dom(input)?;
-        let new_document = sanitize::sanitize_dom(&dom, rules);
-        let result_bytes = parse::unparse_document(&new_document)?;
-        Ok(result_bytes)
+    let document = Document::parse_bytes(limit_input(rules, input)?);
+    let (new_document, report) = sanitize::sanitize_document(&document, rules, rules.strict)?;
+    if rules.strict {
+        check_strict(report)?;
+        return Ok(input.to_vec());
     }
-    inner(rules, input).map_err(SanitizeError)
+    let result_bytes = new_document.to_bytes().map_err(SanitizeError::Serialize)?;
+    Ok(result_bytes)
+}
+
+/// Sanitize HTML bytes in a legacy encoding
+///
+/// Input is decoded with an encoding from `options` or with a detected one, the result is
+/// encoded with an output encoding of `options`. In strict mode the result is the input
+/// re-encoded with the output encoding. See [`encoding`] for an example.
+#[cfg(feature = "encoding")]
+pub fn sanitize_encoded(
+    rules: &Rules,
+    input: &[u8],
+    options: &encoding::EncodingOptions,
+) -> Result<Vec<u8>, SanitizeError> {
+    let input = limit_input(rules, input)?;
+    let encoding = options.input.unwrap_or_else(|| encoding::sniff(input));
+    let document = Document::parse_bytes_encoded(input, encoding);
+    let (new_document, report) = sanitize::sanitize_document(&document, rules, rules.strict)?;
+    if rules.strict {
+        check_strict(report)?;
+        let (input, _) = encoding.decode_with_bom_removal(input);
+        return Ok(encoding::encode(input.as_bytes(), options.output));
+    }
+    let result_bytes = new_document.to_bytes().map_err(SanitizeError::Serialize)?;
+    Ok(encoding::encode(&result_bytes, options.output))
+}
+
+/// Sanitize HTML read from `input` and write the result to `output` as it goes
+///
+/// Unlike [`sanitize_bytes`] it does not build a document tree: elements are matched by a stack
+/// of open elements, so memory use depends on nesting depth rather than on the input size.
+/// Elements are closed by start tags the way a browser closes them, e.g. an open `p` before
+/// a `div` or a previous `li` before a next one. Misnested markup is not repaired the way
+/// a browser repairs it, e.g. content of a table is not moved out of it, but the result is
+/// well-formed and allows only what the rules allow.
+///
+/// In strict mode it fails on the first violation and the output written so far should be
+/// discarded.
+///
+/// # Examples
+///
+/// ```
+/// use sanitize_html::sanitize_stream;
+/// use sanitize_html::rules::predefined::BASIC;
+///
+/// let input = "<p>Hello <b onclick=\"steal()\">world</b><iframe></iframe>".as_bytes();
+/// let mut output = Vec::new();
+/// sanitize_stream(&BASIC, input, &mut output).unwrap();
+/// assert_eq!(output, b"<p>Hello <b>world</b></p>");
+/// ```
+pub fn sanitize_stream<R: Read, W: Write>(
+    rules: &Rules,
+    input: R,
+    output: W,
+) -> Result<(), SanitizeError> {
+    stream::sanitize_stream(rules, input, output)
 }
 
 /// Sanitize HTML string
 pub fn sanitize_str(rules: &Rules, input: &str) -> Result<String, SanitizeError> {
     let result_bytes = sanitize_bytes(rules, input.as_bytes())?;
-    let result_string = String::from_utf8(result_bytes).map_err(|e| SanitizeError(Box::new(e)))?;
+    let result_string = String::from_utf8(result_bytes).map_err(SanitizeError::Utf8)?;
+    Ok(result_string)
+}
+
+/// Sanitizes a string and reports changes, including a cut of the input.
+fn sanitize_reported(rules: &Rules, input: &str) -> Result<(Document, Report), SanitizeError> {
+    let limited = limit_input_str(rules, input)?;
+    let (new_document, report) =
+        sanitize::sanitize_document(&Document::parse(limited), rules, true)?;
+    let mut report = report.unwrap_or_default();
+    if let Some(max) = rules.limits.max_input_bytes
+        && limited.len() < input.len()
+    {
+        let kind = ChangeKind::LimitExceeded {
+            limit: Limit::InputSize,
+            max,
+        };
+        report.changes.insert(
+            0,
+            Change {
+                path: "/".to_owned(),
+                kind,
+            },
+        );
+    }
+    Ok((new_document, report))
+}
+
+/// Sanitize HTML string and report every change made to it
+pub fn sanitize_with_report(rules: &Rules, input: &str) -> Result<(String, Report), SanitizeError> {
+    let (new_document, report) = sanitize_reported(rules, input)?;
+    if rules.strict {
+        check_strict(Some(report))?;
+        return Ok((input.to_owned(), Report::default()));
+    }
+    Ok((serialize(&new_document)?, report))
+}
+
+/// Check HTML string against the rules without changing it
+///
+/// Returns every change sanitization would make as [`SanitizeError::Policy`], the same way
+/// strict mode does. Input is valid if there are none. Other errors, e.g. a limit which fails
+/// sanitization, are returned as they are, so the error type is [`SanitizeError`] rather than
+/// a bare list of violations.
+///
+/// # Examples
+///
+/// ```
+/// use sanitize_html::validate;
+/// use sanitize_html::errors::SanitizeError;
+/// use sanitize_html::rules::predefined::BASIC;
+///
+/// assert!(validate(&BASIC, "<p>Hello <b>world</b></p>").is_ok());
+///
+/// match validate(&BASIC, "<p onclick=\"steal()\">Hello</p>") {
+///     Err(SanitizeError::Policy(violations)) => assert_eq!(violations.len(), 1),
+///     other => panic!("unexpected result: {:?}", other),
+/// }
+/// ```
+pub fn validate(rules: &Rules, input: &str) -> Result<(), SanitizeError> {
+    let (_, report) = sanitize_reported(rules, input)?;
+    match report.is_empty() {
+        true => Ok(()),
+        false => Err(SanitizeError::Policy(report.changes)),
+    }
+}
+
+/// Sanitize HTML bytes as a fragment which will be inserted into a `context` element
+///
+/// The input is parsed the same way a browser parses markup assigned to `innerHTML` of the
+/// `context` element, so e.g. table cells survive inside a `tr` and markup inside a `textarea`
+/// becomes plain text.
+///
+/// A fragment for an element whose content is not escaped, e.g. `script` or `iframe`, is text:
+/// end tags of the element in it are escaped, and a fragment for `style` is sanitized
+/// as a stylesheet.
+pub fn sanitize_fragment_bytes(
+    rules: &Rules,
+    context: &str,
+    input: &[u8],
+) -> Result<Vec<u8>, SanitizeError> {
+    let document = Document::parse_fragment_bytes(context, limit_input(rules, input)?);
+    let (new_document, report) = sanitize::sanitize_document(&document, rules, rules.strict)?;
+    if rules.strict {
+        check_strict(report)?;
+        return Ok(input.to_vec());
+    }
+    let result_bytes = new_document.to_bytes().map_err(SanitizeError::Serialize)?;
+    Ok(result_bytes)
+}
+
+/// Sanitize HTML string as a fragment which will be inserted into a `context` element
+///
+/// # Examples
+///
+/// ```
+/// use sanitize_html::sanitize_fragment;
+/// use sanitize_html::rules::predefined::RELAXED;
+///
+/// let cells = sanitize_fragment(&RELAXED, "tr", "<td>a</td><td>b</td>").unwrap();
+/// assert_eq!(&cells, "<td>a</td><td>b</td>");
+/// ```
+pub fn sanitize_fragment(
+    rules: &Rules,
+    context: &str,
+    input: &str,
+) -> Result<String, SanitizeError> {
+    let result_bytes = sanitize_fragment_bytes(rules, context, input.as_bytes())?;
+    let result_string = String::from_utf8(result_bytes).map_err(SanitizeError::Utf8)?;
     Ok(result_string)
 }
@@ -1,38 +1,65 @@
 use crate::rcdom::{Node, RcDom, SerializableHandle};
 use html5ever::driver::ParseOpts;
-use html5ever::parse_document;
+use html5ever::{LocalName, parse_document, parse_fragment};
 use html5ever::{
     interface::QualName,
-    local_name, namespace_prefix, ns, serialize,
+    namespace_prefix, ns, serialize,
     serialize::{SerializeOpts, TraversalScope},
-    tendril::TendrilSink,
+    tendril::{ByteTendril, TendrilSink},
 };
 use std::default::Default;
-use std::error::Error;
-use std::io::Cursor;
+use std::io;
 use std::rc::Rc;
 
-pub(crate) fn parse_dom(input: &[u8]) -> Result<RcDom, Box<dyn Error>> {
+fn parse_opts() -> ParseOpts {
     let mut opts = ParseOpts::default();
     opts.tree_builder.drop_doctype = true;
+    opts
+}
 
-    let mut cursor = Cursor::new(input);
-
-    let dom = parse_document(RcDom::default(), opts)
+pub(crate) fn parse_dom(input: &[u8]) -> RcDom {
+    parse_document(RcDom::default(), parse_opts())
         .from_utf8()
-        .read_from(&mut cursor)?;
+        .one(ByteTendril::from_slice(input))
+}
 
-    Ok(dom)
+#[cfg(feature = "encoding")]
+pub(crate) fn parse_dom_encoded(input: &[u8], encoding: &'static encoding_rs::Encoding) -> RcDom {
+    tendril::stream::LossyDecoder::new_encoding_rs(
+        encoding,
+        parse_document(RcDom::default(), parse_opts()),
+    )
+    .one(ByteTendril::from_slice(input))
 }
 
-pub(crate) fn unparse_document(document: &Rc<Node>) -> Result<Vec<u8>, Box<dyn Error>> {
-    let mut buf: Vec<u8> = Vec::new();
+pub(crate) fn parse_fragment_dom(input: &[u8], context: &str) -> RcDom {
+    parse_fragment(
+        RcDom::default(),
+        parse_opts(),
+        context_name(context),
+        Vec::new(),
+        false,
+    )
+    .from_utf8()
+    .one(ByteTendril::from_slice(input))
+}
 
-    let parent = QualName::new(
+pub(crate) fn context_name(context: &str) -> QualName {
+    QualName::new(
         Some(namespace_prefix!("html")),
         ns!(html),
-        local_name!("div"),
-    );
+        LocalName::from(context.to_ascii_lowercase()),
+    )
+}
+
+pub(crate) fn unparse_document(document: &Rc<Node>) -> io::Result<Vec<u8>> {
+    unparse_fragment(document, "div")
+}
+
+pub(crate) fn unparse_fragment(document: &Rc<Node>, context: &str) -> io::Result<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::new();
+
+    let parent = context_name(context);
 
     let opts = SerializeOpts {
         scripting_enabled: false,
@@ -68,6 +68,7 @@ pub enum NodeData {
     /// [document type declaration on wikipedia][dtd wiki].
     ///
     /// [dtd wiki]: https://en.wikipedia.org/wiki/Document_type_declaration
+    #[allow(dead_code)]
     Doctype {
         name: StrTendril,
         public_id: StrTendril,
@@ -122,9 +123,18 @@ impl Node {
             children: RefCell::new(Vec::new()),
         })
     }
+
+    /// Get a parent of the node, if any.
+    pub fn parent_node(&self) -> Option<Handle> {
+        let weak = self.parent.take()?;
+        let parent = weak.upgrade();
+        self.parent.set(Some(weak));
+        parent
+    }
 }
 
 impl Drop for Node {
+    #[allow(clippy::collapsible_if, clippy::useless_conversion)]
     fn drop(&mut self) {
         let mut nodes = mem::take(&mut *self.children.borrow_mut());
         while let Some(node) = nodes.pop() {
@@ -159,7 +169,7 @@ pub type Handle = Rc<Node>;
 pub type WeakHandle = Weak<Node>;
 
 /// Append a parentless node to another nodes' children
-fn append(new_parent: &Handle, child: Handle) {
+pub(crate) fn append(new_parent: &Handle, child: Handle) {
     let previous_parent = child.parent.replace(Some(Rc::downgrade(new_parent)));
     // Invariant: child cannot have existing parent
     assert!(previous_parent.is_none());
@@ -197,7 +207,7 @@ fn append_to_existing_text(prev: &Handle, text: &str) -> bool {
     }
 }
 
-fn remove_from_parent(target: &Handle) {
+pub(crate) fn remove_from_parent(target: &Handle) {
     if let Some((parent, i)) = get_parent_and_index(target) {
         parent.children.borrow_mut().remove(i);
         target.parent.set(None);
@@ -292,6 +302,7 @@ impl TreeSink for RcDom {
         })
     }
 
+    #[allow(clippy::collapsible_if)]
     fn append(&self, parent: &Handle, child: NodeOrText<Handle>) {
         // Append to an existing Text node if we have one.
         if let NodeOrText::AppendText(text) = &child {
@@ -444,7 +455,8 @@ enum SerializeOp {
     Close(QualName),
 }
 
-pub struct SerializableHandle(Handle);
+/// Node which can be passed to html5ever serializer.
+pub(crate) struct SerializableHandle(Handle);
 
 impl From<Handle> for SerializableHandle {
     fn from(h: Handle) -> SerializableHandle {
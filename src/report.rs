@@ -0,0 +1,282 @@
+//! Report of changes made to a document by sanitization.
+//!
+//! # Examples
+//!
+//! ```
+//! use sanitize_html::sanitize_with_report;
+//! use sanitize_html::report::ChangeKind;
+//! use sanitize_html::rules::predefined::BASIC;
+//!
+//! let (output, report) = sanitize_with_report(&BASIC, "<b onclick=\"go()\">hi</b><iframe></iframe>").unwrap();
+//! assert_eq!(&output, "<b>hi</b>");
+//! assert_eq!(report.changes.len(), 2);
+//! assert_eq!(report.changes[1].path, "/html[1]/body[1]/iframe[1]");
+//! assert!(matches!(report.changes[1].kind, ChangeKind::ElementElided { .. }));
+//! ```
+
+use crate::errors::Limit;
+use crate::rules::RuleScope;
+use std::fmt;
+
+/// Reason why an attribute was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Rejection {
+    /// There is no rule for this attribute.
+    NotAllowed,
+    /// Value of the attribute does not match a pattern of a rule.
+    PatternMismatch {
+        /// where the rule is defined
+        scope: RuleScope,
+        /// attribute name or wildcard of the rule
+        rule: String,
+        /// description of the pattern, `None` for a rule which transforms a value
+        pattern: Option<String>,
+    },
+    /// None of classes is allowed by a class policy.
+    NoAllowedClasses,
+    /// None of `rel` tokens is allowed by a link policy, and it adds none.
+    NoAllowedLinkTypes,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::NotAllowed => write!(f, "not allowed"),
+            Rejection::PatternMismatch {
+                scope,
+                rule,
+                pattern,
+            } => {
+                let scope = match scope {
+                    RuleScope::Element => "element",
+                    RuleScope::Global => "global",
+                };
+                write!(f, "value does not match {} rule `{}`", scope, rule)?;
+                match pattern {
+                    Some(pattern) => write!(f, ": {}", pattern),
+                    None => Ok(()),
+                }
+            }
+            Rejection::NoAllowedClasses => write!(f, "no allowed classes"),
+            Rejection::NoAllowedLinkTypes => write!(f, "no allowed link types"),
+        }
+    }
+}
+
+/// Kind of a change
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChangeKind {
+    /// Element was removed together with its children.
+    ElementDropped {
+        /// name of an element
+        name: String,
+    },
+    /// Element was removed, its children were kept.
+    ElementElided {
+        /// name of an element
+        name: String,
+    },
+    /// Element was replaced by spaces, its children were kept.
+    ElementSpaced {
+        /// name of an element
+        name: String,
+    },
+    /// Element was renamed.
+    ElementRenamed {
+        /// original name of an element
+        from: String,
+        /// new name of an element
+        to: String,
+    },
+    /// Element was replaced with other nodes by a transformer.
+    ElementReplaced {
+        /// name of an element
+        name: String,
+    },
+    /// Attribute was removed from an element.
+    AttributeRejected {
+        /// name of an element
+        element: String,
+        /// name of an attribute
+        name: String,
+        /// value of an attribute
+        value: String,
+        /// reason of a rejection
+        reason: Rejection,
+    },
+    /// Value of an attribute was rewritten by a rule.
+    AttributeChanged {
+        /// name of an element
+        element: String,
+        /// name of an attribute
+        name: String,
+        /// original value of an attribute
+        from: String,
+        /// new value of an attribute
+        to: String,
+    },
+    /// CSS rule was removed from `style` element.
+    CssRuleRejected {
+        /// name of an element
+        element: String,
+        /// selectors or at-rule
+        rule: String,
+    },
+    /// CSS declaration was removed from `style` attribute or element.
+    DeclarationRejected {
+        /// name of an element
+        element: String,
+        /// name of a property
+        property: String,
+        /// value of a property
+        value: String,
+    },
+    /// Content of `style` element was rewritten, e.g. scoped or reformatted,
+    /// without removing rules or declarations.
+    StylesheetChanged {
+        /// name of an element
+        element: String,
+        /// original stylesheet
+        from: String,
+        /// new stylesheet
+        to: String,
+    },
+    /// End tag in text of an element, whose content is not escaped, was escaped,
+    /// so the text can not close the element.
+    RawTextEscaped {
+        /// name of an element
+        element: String,
+        /// original text
+        from: String,
+        /// new text
+        to: String,
+    },
+    /// Mandatory attribute was added to an element.
+    AttributeAdded {
+        /// name of an element
+        element: String,
+        /// name of an attribute
+        name: String,
+        /// value of an attribute
+        value: String,
+    },
+    /// Content which exceeds a resource limit was removed.
+    LimitExceeded {
+        /// limit which was exceeded
+        limit: Limit,
+        /// configured maximum
+        max: usize,
+    },
+    /// Comment was removed.
+    CommentStripped {
+        /// text of a comment
+        text: String,
+    },
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeKind::ElementDropped { name } => {
+                write!(f, "element <{}> removed with its content", name)
+            }
+            ChangeKind::ElementElided { name } => write!(f, "element <{}> removed", name),
+            ChangeKind::ElementSpaced { name } => {
+                write!(f, "element <{}> replaced with spaces", name)
+            }
+            ChangeKind::ElementRenamed { from, to } => {
+                write!(f, "element <{}> renamed to <{}>", from, to)
+            }
+            ChangeKind::ElementReplaced { name } => write!(f, "element <{}> replaced", name),
+            ChangeKind::AttributeRejected {
+                element,
+                name,
+                value,
+                reason,
+            } => write!(
+                f,
+                "attribute {}={:?} removed from <{}>: {}",
+                name, value, element, reason
+            ),
+            ChangeKind::AttributeChanged {
+                element,
+                name,
+                from,
+                to,
+            } => write!(
+                f,
+                "attribute {} of <{}> changed from {:?} to {:?}",
+                name, element, from, to
+            ),
+            ChangeKind::DeclarationRejected {
+                element,
+                property,
+                value,
+            } => write!(
+                f,
+                "declaration `{}: {}` removed from <{}>",
+                property, value, element
+            ),
+            ChangeKind::CssRuleRejected { element, rule } => {
+                write!(f, "rule `{}` removed from <{}>", rule, element)
+            }
+            ChangeKind::StylesheetChanged { element, .. } => {
+                write!(f, "stylesheet of <{}> rewritten", element)
+            }
+            ChangeKind::RawTextEscaped { element, .. } => {
+                write!(f, "end tag in text of <{}> escaped", element)
+            }
+            ChangeKind::AttributeAdded {
+                element,
+                name,
+                value,
+            } => write!(f, "attribute {}={:?} added to <{}>", name, value, element),
+            ChangeKind::LimitExceeded { limit, max } => {
+                write!(f, "{} exceeds the limit of {}", limit, max)
+            }
+            ChangeKind::CommentStripped { .. } => write!(f, "comment removed"),
+        }
+    }
+}
+
+/// Change made to a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Location of the affected node in the source document.
+    ///
+    /// This is an XPath-like expression, e.g. `/html[1]/body[1]/div[2]/iframe[1]`.
+    /// Attributes are reported at the path of their element.
+    pub path: String,
+    /// What was changed.
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// Change which sanitization would make to a document, reported by strict mode and
+/// [`validate`](crate::validate) instead of being applied.
+pub type Violation = Change;
+
+/// List of changes made by sanitization
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Changes in document order.
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    /// Returns `true` if sanitization did not change anything.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, path: String, kind: ChangeKind) {
+        self.changes.push(Change { path, kind });
+    }
+}
@@ -0,0 +1,114 @@
+//! Custom handling of elements.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::sanitize_str;
+//! use sanitize_html::rules::action::{Content, ElementAction};
+//! use sanitize_html::rules::pattern::Pattern;
+//! use sanitize_html::rules::{Element, Rules};
+//!
+//! let rules = Rules::new()
+//!     .element(Element::new("a").attribute("href", Pattern::any()))
+//!     .element(Element::new("img").attribute("alt", Pattern::any()))
+//!     .transformer(|element| match element.name {
+//!         "iframe" => {
+//!             let src = element.attribute("src")?;
+//!             src.starts_with("https://www.youtube.com/").then(|| {
+//!                 ElementAction::Replace(vec![Content::element(
+//!                     "a",
+//!                     vec![("href", src)],
+//!                     vec![Content::text("Watch on YouTube")],
+//!                 )])
+//!             })
+//!         }
+//!         "img" if element.attribute("alt").is_none() => Some(ElementAction::Delete),
+//!         _ => None,
+//!     });
+//!
+//! assert_eq!(
+//!     sanitize_str(&rules, "<iframe src=\"https://www.youtube.com/embed/x\"></iframe><img src=\"a.png\">").unwrap(),
+//!     "<a href=\"https://www.youtube.com/embed/x\">Watch on YouTube</a>"
+//! );
+//! ```
+
+use super::RenameAttributes;
+use std::sync::Arc;
+
+/// Element as it is seen by a transformer
+pub struct ElementContext<'a> {
+    /// name of an element
+    pub name: &'a str,
+    /// attributes of an element as they are in a source document
+    pub attributes: &'a [(String, String)],
+    /// names of ancestors of an element in a source document, the outermost one first
+    pub ancestors: &'a [String],
+}
+
+impl ElementContext<'_> {
+    /// Returns a value of an attribute.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attr_name, _)| attr_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Node which replaces an element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    /// Text node
+    Text(String),
+    /// Element with attributes and children
+    Element {
+        /// name of an element
+        name: String,
+        /// attributes of an element
+        attributes: Vec<(String, String)>,
+        /// children of an element
+        children: Vec<Content>,
+    },
+}
+
+impl Content {
+    /// Creates a text node.
+    pub fn text(text: &str) -> Self {
+        Content::Text(text.to_owned())
+    }
+
+    /// Creates an element.
+    pub fn element(name: &str, attributes: Vec<(&str, &str)>, children: Vec<Content>) -> Self {
+        Content::Element {
+            name: name.to_owned(),
+            attributes: attributes
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+            children,
+        }
+    }
+}
+
+/// What to do with an element
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ElementAction {
+    /// Keeps an element. Its attributes are checked by a rule of the element
+    /// or, if it is not an allowed element, by global attribute rules only.
+    Keep,
+    /// Removes an element together with its children.
+    Delete,
+    /// Replaces an element with spaces, its children are kept.
+    Space,
+    /// Removes an element, its children are kept.
+    Elide,
+    /// Renames an element. Its attributes are removed.
+    Rename(String),
+    /// Renames an element, keeping or mapping its attributes.
+    RenameWith(String, RenameAttributes),
+    /// Replaces an element with nodes. The nodes are inserted as is, without sanitization.
+    Replace(Vec<Content>),
+}
+
+/// Function which decides what to do with an element. `None` leaves it to other rules.
+pub type ElementTransformer = Arc<dyn Fn(&ElementContext) -> Option<ElementAction> + Sync + Send>;
@@ -0,0 +1,59 @@
+//! Rules to filter `class` attribute token by token.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::rules::class::ClassPolicy;
+//! use regex::Regex;
+//!
+//! let classes = ClassPolicy::new()
+//!     .name("highlight")
+//!     .pattern(Regex::new("^language-[a-z0-9]+$").unwrap());
+//!
+//! assert_eq!(classes.filter("language-rust big highlight").as_deref(), Some("language-rust highlight"));
+//! assert_eq!(classes.filter("big red"), None);
+//! ```
+
+use regex::Regex;
+use std::collections::HashSet;
+
+/// structure to describe allowed classes
+#[derive(Clone, Default)]
+pub struct ClassPolicy {
+    /// Allowed class names.
+    pub names: HashSet<String>,
+    /// Patterns of allowed class names. A pattern should be anchored to match a whole name.
+    pub patterns: Vec<Regex>,
+}
+
+impl ClassPolicy {
+    /// Creates a policy which allows no classes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an allowed class name
+    pub fn name(mut self, name: &str) -> Self {
+        self.names.insert(name.to_owned());
+        self
+    }
+
+    /// Adds a pattern of allowed class names
+    pub fn pattern(mut self, pattern: Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    /// Checks if a class name is allowed.
+    pub fn allows(&self, class: &str) -> bool {
+        self.names.contains(class) || self.patterns.iter().any(|re| re.is_match(class))
+    }
+
+    /// Removes classes which are not allowed. Returns `None` if no classes are left.
+    pub fn filter(&self, value: &str) -> Option<String> {
+        let classes: Vec<&str> = value
+            .split_ascii_whitespace()
+            .filter(|class| self.allows(class))
+            .collect();
+        (!classes.is_empty()).then(|| classes.join(" "))
+    }
+}
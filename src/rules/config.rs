@@ -0,0 +1,560 @@
+//! Declarative description of rules, which can be loaded from configuration files.
+//!
+//! Available with the `serde` feature. [`Rules`] are serialized through [`RulesConfig`], which
+//! fails for rules holding closures, e.g. custom patterns or transformers.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::sanitize_str;
+//! use sanitize_html::rules::Rules;
+//!
+//! let rules: Rules = serde_json::from_str(r#"{
+//!     "elements": {
+//!         "a": {
+//!             "attributes": {
+//!                 "href": { "url": { "schemes": ["https"] } }
+//!             },
+//!             "mandatory_attributes": { "rel": "nofollow" }
+//!         },
+//!         "img": {
+//!             "attributes": {
+//!                 "src": { "url": { "schemes": ["https"] } },
+//!                 "width": { "range": { "min": 1, "max": 640 } },
+//!                 "align": { "one_of": ["left", "right"] }
+//!             }
+//!         }
+//!     },
+//!     "delete": ["script"]
+//! }"#).unwrap();
+//!
+//! assert_eq!(
+//!     sanitize_str(&rules, "<a href=\"https://example.com/\">a</a><img width=\"1000\" align=\"left\"><script>x</script>").unwrap(),
+//!     "<a href=\"https://example.com/\" rel=\"nofollow\">a</a><img align=\"left\">"
+//! );
+//! ```
+
+use super::class::ClassPolicy;
+use super::css::CssRules;
+use super::limits::Limits;
+use super::link::LinkPolicy;
+use super::pattern::AttributeRule;
+use super::pattern::Pattern;
+use super::url::UrlPolicy;
+use super::{Element, MandatoryAttribute, MandatoryMode, RenameAttributes, Rules};
+use ::url::Url;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::ops::Bound;
+
+/// Error of building rules from a description
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Regular expression could not be compiled.
+    Regex(regex::Error),
+    /// Base URL is not a valid absolute URL.
+    BaseUrl(::url::ParseError),
+    /// Rules hold something which has no description, e.g. a closure.
+    NotDescribable(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Regex(error) => write!(f, "invalid regular expression: {}", error),
+            ConfigError::BaseUrl(error) => write!(f, "invalid base URL: {}", error),
+            ConfigError::NotDescribable(what) => write!(f, "{} can not be described", what),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Regex(error) => Some(error),
+            ConfigError::BaseUrl(error) => Some(error),
+            ConfigError::NotDescribable(_) => None,
+        }
+    }
+}
+
+impl From<regex::Error> for ConfigError {
+    fn from(error: regex::Error) -> Self {
+        ConfigError::Regex(error)
+    }
+}
+
+/// Description of a [`Pattern`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternConfig {
+    /// [`Pattern::any`]
+    Any,
+    /// [`Pattern::regex`]
+    Regex(String),
+    /// [`Pattern::one_of`]
+    OneOf(Vec<String>),
+    /// [`Pattern::url`]
+    Url(UrlPolicy),
+    /// [`Pattern::range`] with inclusive bounds
+    Range {
+        /// Lower bound
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        /// Upper bound
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+    },
+    /// Negation of a pattern
+    Not(Box<PatternConfig>),
+    /// Value matches all patterns
+    And(Vec<PatternConfig>),
+    /// Value matches any of patterns
+    Or(Vec<PatternConfig>),
+}
+
+fn inclusive(bound: Option<f64>) -> Bound<f64> {
+    bound.map_or(Bound::Unbounded, Bound::Included)
+}
+
+impl TryFrom<&PatternConfig> for Pattern {
+    type Error = ConfigError;
+
+    fn try_from(config: &PatternConfig) -> Result<Self, Self::Error> {
+        Ok(match config {
+            PatternConfig::Any => Pattern::any(),
+            PatternConfig::Regex(regex) => Pattern::regex(Regex::new(regex)?),
+            PatternConfig::OneOf(values) => Pattern::one_of(values.clone()),
+            PatternConfig::Url(policy) => Pattern::url(policy.clone()),
+            PatternConfig::Range { min, max } => Pattern::range((inclusive(*min), inclusive(*max))),
+            PatternConfig::Not(pattern) => !Pattern::try_from(&**pattern)?,
+            PatternConfig::And(patterns) => Pattern::And(patterns_from(patterns)?),
+            PatternConfig::Or(patterns) => Pattern::Or(patterns_from(patterns)?),
+        })
+    }
+}
+
+fn patterns_from(configs: &[PatternConfig]) -> Result<Vec<Pattern>, ConfigError> {
+    configs.iter().map(Pattern::try_from).collect()
+}
+
+fn inclusive_bound(bound: &Bound<f64>) -> Result<Option<f64>, ConfigError> {
+    match bound {
+        Bound::Included(bound) => Ok(Some(*bound)),
+        Bound::Unbounded => Ok(None),
+        Bound::Excluded(_) => Err(ConfigError::NotDescribable("exclusive range bound")),
+    }
+}
+
+impl TryFrom<&Pattern> for PatternConfig {
+    type Error = ConfigError;
+
+    fn try_from(pattern: &Pattern) -> Result<Self, Self::Error> {
+        Ok(match pattern {
+            Pattern::Any => PatternConfig::Any,
+            Pattern::Regex(regex) => PatternConfig::Regex(regex.as_str().to_owned()),
+            Pattern::OneOf(values) => PatternConfig::OneOf(values.clone()),
+            Pattern::Url(policy) => PatternConfig::Url(policy.clone()),
+            Pattern::Range(min, max) => PatternConfig::Range {
+                min: inclusive_bound(min)?,
+                max: inclusive_bound(max)?,
+            },
+            Pattern::Not(pattern) => PatternConfig::Not(Box::new(pattern.as_ref().try_into()?)),
+            Pattern::And(patterns) => PatternConfig::And(configs_from(patterns)?),
+            Pattern::Or(patterns) => PatternConfig::Or(configs_from(patterns)?),
+            Pattern::Custom(_) => return Err(ConfigError::NotDescribable("custom pattern")),
+        })
+    }
+}
+
+fn configs_from(patterns: &[Pattern]) -> Result<Vec<PatternConfig>, ConfigError> {
+    patterns.iter().map(PatternConfig::try_from).collect()
+}
+
+impl TryFrom<&AttributeRule> for PatternConfig {
+    type Error = ConfigError;
+
+    fn try_from(rule: &AttributeRule) -> Result<Self, Self::Error> {
+        match rule {
+            AttributeRule::Pattern(pattern) => pattern.try_into(),
+            AttributeRule::Transform(_) => {
+                Err(ConfigError::NotDescribable("attribute transformer"))
+            }
+        }
+    }
+}
+
+fn attribute_configs<'a>(
+    rules: impl IntoIterator<Item = (&'a String, &'a AttributeRule)>,
+) -> Result<Vec<(String, PatternConfig)>, ConfigError> {
+    rules
+        .into_iter()
+        .map(|(name, rule)| Ok((name.clone(), rule.try_into()?)))
+        .collect()
+}
+
+/// Description of a [`ClassPolicy`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassPolicyConfig {
+    /// Allowed class names
+    pub names: BTreeSet<String>,
+    /// Regular expressions of allowed class names
+    pub patterns: Vec<String>,
+}
+
+impl TryFrom<&ClassPolicyConfig> for ClassPolicy {
+    type Error = ConfigError;
+
+    fn try_from(config: &ClassPolicyConfig) -> Result<Self, Self::Error> {
+        let policy = config
+            .names
+            .iter()
+            .fold(ClassPolicy::new(), |policy, name| policy.name(name));
+        config.patterns.iter().try_fold(policy, |policy, pattern| {
+            Ok(policy.pattern(Regex::new(pattern)?))
+        })
+    }
+}
+
+impl From<&ClassPolicy> for ClassPolicyConfig {
+    fn from(policy: &ClassPolicy) -> Self {
+        ClassPolicyConfig {
+            names: policy.names.iter().cloned().collect(),
+            patterns: policy
+                .patterns
+                .iter()
+                .map(|pattern| pattern.as_str().to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// Description of [`CssRules`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CssRulesConfig {
+    /// Allowed properties and patterns for their values
+    pub properties: BTreeMap<String, PatternConfig>,
+    /// URLs allowed in `url()`
+    pub urls: UrlPolicy,
+    /// Allowed at-rules
+    pub at_rules: BTreeSet<String>,
+    /// Selector which prefixes every selector of a stylesheet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl TryFrom<&CssRulesConfig> for CssRules {
+    type Error = ConfigError;
+
+    fn try_from(config: &CssRulesConfig) -> Result<Self, Self::Error> {
+        let mut rules = CssRules::new().urls(config.urls.clone());
+        for (property, pattern) in &config.properties {
+            rules = rules.property(property, pattern.try_into()?);
+        }
+        for name in &config.at_rules {
+            rules = rules.at_rule(name);
+        }
+        if let Some(ref scope) = config.scope {
+            rules = rules.scope(scope);
+        }
+        Ok(rules)
+    }
+}
+
+impl TryFrom<&CssRules> for CssRulesConfig {
+    type Error = ConfigError;
+
+    fn try_from(rules: &CssRules) -> Result<Self, Self::Error> {
+        Ok(CssRulesConfig {
+            properties: rules
+                .properties
+                .iter()
+                .map(|(property, pattern)| Ok((property.clone(), pattern.try_into()?)))
+                .collect::<Result<_, ConfigError>>()?,
+            urls: rules.urls.clone(),
+            at_rules: rules.at_rules.iter().cloned().collect(),
+            scope: rules.scope.clone(),
+        })
+    }
+}
+
+/// Description of a mandatory attribute: either a value which replaces a value from
+/// a document, or a value with a [`MandatoryMode`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MandatoryConfig {
+    /// Value which replaces a value from a document
+    Value(String),
+    /// Value with a mode
+    WithMode {
+        /// Mandatory value
+        value: String,
+        /// How the value is combined with a value from a document
+        mode: MandatoryMode,
+    },
+}
+
+impl From<&MandatoryAttribute> for MandatoryConfig {
+    fn from(mandatory: &MandatoryAttribute) -> Self {
+        match mandatory.mode {
+            MandatoryMode::Override => MandatoryConfig::Value(mandatory.value.clone()),
+            mode => MandatoryConfig::WithMode {
+                value: mandatory.value.clone(),
+                mode,
+            },
+        }
+    }
+}
+
+/// Description of an allowed [`Element`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ElementConfig {
+    /// Allowed attributes
+    pub attributes: BTreeMap<String, PatternConfig>,
+    /// Allowed attributes given by wildcards, in order of precedence
+    pub wildcard_attributes: Vec<(String, PatternConfig)>,
+    /// Mandatory attributes and their values
+    pub mandatory_attributes: BTreeMap<String, MandatoryConfig>,
+    /// Rules to sanitize `style` attribute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<CssRulesConfig>,
+    /// Classes allowed in `class` attribute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<ClassPolicyConfig>,
+}
+
+impl ElementConfig {
+    fn build(&self, name: &str) -> Result<Element, ConfigError> {
+        let mut element = Element::new(name);
+        for (attribute, pattern) in &self.attributes {
+            element = element.attribute(attribute, Pattern::try_from(pattern)?);
+        }
+        for (wildcard, pattern) in &self.wildcard_attributes {
+            element = element.attribute_wildcard(wildcard, Pattern::try_from(pattern)?);
+        }
+        for (attribute, mandatory) in &self.mandatory_attributes {
+            element = match mandatory {
+                MandatoryConfig::Value(value) => element.mandatory_attribute(attribute, value),
+                MandatoryConfig::WithMode { value, mode } => {
+                    element.mandatory_attribute_mode(attribute, value, *mode)
+                }
+            };
+        }
+        if let Some(ref style) = self.style {
+            element = element.style(style.try_into()?);
+        }
+        if let Some(ref class) = self.class {
+            element = element.class_policy(class.try_into()?);
+        }
+        Ok(element)
+    }
+}
+
+impl TryFrom<&Element> for ElementConfig {
+    type Error = ConfigError;
+
+    fn try_from(element: &Element) -> Result<Self, Self::Error> {
+        Ok(ElementConfig {
+            attributes: attribute_configs(&element.attributes)?
+                .into_iter()
+                .collect(),
+            wildcard_attributes: attribute_configs(
+                element.wildcard_attributes.iter().map(|(k, v)| (k, v)),
+            )?,
+            mandatory_attributes: element
+                .mandatory_attributes
+                .iter()
+                .map(|(name, mandatory)| (name.clone(), mandatory.into()))
+                .collect(),
+            style: element.style.as_ref().map(TryInto::try_into).transpose()?,
+            class: element.class.as_ref().map(Into::into),
+        })
+    }
+}
+
+/// Description of a rename: either a new name of an element, or a new name
+/// and whether attributes are kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RenameConfig {
+    /// New name of an element, attributes are removed
+    To(String),
+    /// New name of an element and whether attributes are kept
+    WithAttributes {
+        /// New name of an element
+        to: String,
+        /// Determines if attributes allowed by rules of the new element are kept
+        keep_attributes: bool,
+    },
+}
+
+/// Description of [`Rules`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Determines if comments are kept
+    pub allow_comments: bool,
+    /// Determines if a document which needs sanitization is rejected
+    pub strict: bool,
+    /// Allowed elements by name
+    pub elements: BTreeMap<String, ElementConfig>,
+    /// Attributes allowed on every allowed element
+    pub global_attributes: BTreeMap<String, PatternConfig>,
+    /// Attributes given by wildcards which are allowed on every allowed element
+    pub global_wildcard_attributes: Vec<(String, PatternConfig)>,
+    /// Elements which are removed together with their children
+    pub delete: BTreeSet<String>,
+    /// Elements which are replaced by spaces
+    pub space: BTreeSet<String>,
+    /// Elements which are renamed
+    pub rename: BTreeMap<String, RenameConfig>,
+    /// Rules to sanitize content of `style` elements
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stylesheet: Option<CssRulesConfig>,
+    /// URL which relative URLs are resolved against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Classes allowed in `class` attribute of every allowed element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_policy: Option<ClassPolicyConfig>,
+    /// Rules for `rel` and `target` attributes of links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_policy: Option<LinkPolicy>,
+    /// Resource limits
+    pub limits: Limits,
+}
+
+impl TryFrom<&RulesConfig> for Rules {
+    type Error = ConfigError;
+
+    fn try_from(config: &RulesConfig) -> Result<Self, Self::Error> {
+        let mut rules = Rules::new()
+            .allow_comments(config.allow_comments)
+            .strict(config.strict)
+            .limits(config.limits);
+        for (name, element) in &config.elements {
+            rules = rules.element(element.build(name)?);
+        }
+        for (attribute, pattern) in &config.global_attributes {
+            rules = rules.global_attribute(attribute, Pattern::try_from(pattern)?);
+        }
+        for (wildcard, pattern) in &config.global_wildcard_attributes {
+            rules = rules.global_attribute_wildcard(wildcard, Pattern::try_from(pattern)?);
+        }
+        for name in &config.delete {
+            rules = rules.delete(name);
+        }
+        for name in &config.space {
+            rules = rules.space(name);
+        }
+        for (name, rename) in &config.rename {
+            rules = match rename {
+                RenameConfig::To(to) => rules.rename(name, to),
+                RenameConfig::WithAttributes {
+                    to,
+                    keep_attributes: false,
+                } => rules.rename(name, to),
+                RenameConfig::WithAttributes {
+                    to,
+                    keep_attributes: true,
+                } => rules.rename_keeping_attributes(name, to),
+            };
+        }
+        if let Some(ref stylesheet) = config.stylesheet {
+            rules = rules.stylesheet(stylesheet.try_into()?);
+        }
+        if let Some(ref base_url) = config.base_url {
+            rules.base_url = Some(Url::parse(base_url).map_err(ConfigError::BaseUrl)?);
+        }
+        if let Some(ref class_policy) = config.class_policy {
+            rules = rules.class_policy(class_policy.try_into()?);
+        }
+        if let Some(ref link_policy) = config.link_policy {
+            rules = rules.link_policy(link_policy.clone());
+        }
+        Ok(rules)
+    }
+}
+
+impl TryFrom<&Rules> for RulesConfig {
+    type Error = ConfigError;
+
+    fn try_from(rules: &Rules) -> Result<Self, Self::Error> {
+        if rules.transformer.is_some() {
+            return Err(ConfigError::NotDescribable("element transformer"));
+        }
+        Ok(RulesConfig {
+            allow_comments: rules.allow_comments,
+            strict: rules.strict,
+            elements: rules
+                .allowed_elements
+                .iter()
+                .map(|(name, element)| Ok((name.clone(), element.try_into()?)))
+                .collect::<Result<_, ConfigError>>()?,
+            global_attributes: attribute_configs(&rules.global_attributes)?
+                .into_iter()
+                .collect(),
+            global_wildcard_attributes: attribute_configs(
+                rules.global_wildcard_attributes.iter().map(|(k, v)| (k, v)),
+            )?,
+            delete: rules.delete_elements.iter().cloned().collect(),
+            space: rules.space_elements.iter().cloned().collect(),
+            rename: rules
+                .rename_elements
+                .iter()
+                .map(|(name, rename)| {
+                    let config = match rename.attributes {
+                        RenameAttributes::Drop => RenameConfig::To(rename.to.clone()),
+                        RenameAttributes::Keep => RenameConfig::WithAttributes {
+                            to: rename.to.clone(),
+                            keep_attributes: true,
+                        },
+                        RenameAttributes::Map(_) => {
+                            return Err(ConfigError::NotDescribable("attribute mapper"));
+                        }
+                    };
+                    Ok((name.clone(), config))
+                })
+                .collect::<Result<_, ConfigError>>()?,
+            stylesheet: rules
+                .stylesheet
+                .as_ref()
+                .map(TryInto::try_into)
+                .transpose()?,
+            base_url: rules.base_url.as_ref().map(Url::to_string),
+            class_policy: rules.class_policy.as_ref().map(Into::into),
+            link_policy: rules.link_policy.clone(),
+            limits: rules.limits,
+        })
+    }
+}
+
+impl TryFrom<RulesConfig> for Rules {
+    type Error = ConfigError;
+
+    fn try_from(config: RulesConfig) -> Result<Self, Self::Error> {
+        Rules::try_from(&config)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rules {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = RulesConfig::deserialize(deserializer)?;
+        Rules::try_from(&config).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Rules {
+    /// Serializes a description of rules. Fails if rules hold something which has
+    /// no description, see [`ConfigError::NotDescribable`].
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let config = RulesConfig::try_from(self).map_err(serde::ser::Error::custom)?;
+        config.serialize(serializer)
+    }
+}
@@ -0,0 +1,428 @@
+//! Rules to sanitize CSS.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::rules::css::CssRules;
+//! use sanitize_html::rules::pattern::Pattern;
+//! use regex::Regex;
+//!
+//! let css = CssRules::new()
+//!     .property("color", Pattern::any())
+//!     .property("background-image", Pattern::any())
+//!     .property("width", Pattern::regex(Regex::new("^[0-9]+px$").unwrap()))
+//!     .url_scheme("https");
+//!
+//! assert_eq!(
+//!     css.sanitize_style("color: red; width: expression(alert(1)); background-image: url(javascript:alert(1))"),
+//!     "color: red"
+//! );
+//! assert_eq!(
+//!     css.sanitize_style("background-image: url('https://example.com/a.png'); width: 10px"),
+//!     "background-image: url('https://example.com/a.png'); width: 10px"
+//! );
+//! ```
+//!
+//! The same rules can sanitize a whole stylesheet, e.g. content of a `style` element.
+//! ```
+//! use sanitize_html::rules::css::CssRules;
+//! use sanitize_html::rules::pattern::Pattern;
+//!
+//! let css = CssRules::new()
+//!     .property("color", Pattern::any())
+//!     .at_rule("media")
+//!     .scope(".comment");
+//!
+//! assert_eq!(
+//!     css.sanitize_stylesheet("@import url(evil.css); @media print { p, a { color: red; position: fixed } }"),
+//!     "@media print {\n.comment p, .comment a { color: red }\n}"
+//! );
+//! ```
+
+use super::pattern::Pattern;
+use super::url::UrlPolicy;
+use crate::css::{self, Declaration};
+use std::collections::{HashMap, HashSet};
+
+/// Functions which make a browser execute code or load a behavior.
+const FORBIDDEN_FUNCTIONS: &[&str] = &["expression", "javascript", "vbscript"];
+
+/// Properties which make a browser execute code or load a behavior.
+const FORBIDDEN_PROPERTIES: &[&str] = &["behavior", "-moz-binding"];
+
+/// Functions which load an image from a string argument as well as from `url()`.
+const IMAGE_FUNCTIONS: &[&str] = &["image", "image-set", "-webkit-image-set", "cross-fade"];
+
+/// structure to describe CSS sanitization rules
+#[derive(Clone, Default)]
+pub struct CssRules {
+    /// Allowed properties and patterns for their values.
+    pub properties: HashMap<String, Pattern>,
+    /// URLs allowed in `url()` and in strings of `image()` and `image-set()`.
+    pub urls: UrlPolicy,
+    /// At-rules allowed in a stylesheet, e.g. `media` or `font-face`.
+    pub at_rules: HashSet<String>,
+    /// Selector which prefixes every selector of a stylesheet, so its rules
+    /// can not style anything outside of an element matching the selector.
+    pub scope: Option<String>,
+}
+
+/// Part of a stylesheet which was removed by sanitization.
+pub(crate) enum Rejected {
+    Declaration(Declaration),
+    Rule(String),
+}
+
+impl CssRules {
+    /// Creates a new rules set which allows nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an allowed property
+    pub fn property(mut self, property: &str, pattern: Pattern) -> Self {
+        self.properties
+            .insert(property.to_ascii_lowercase(), pattern);
+        self
+    }
+
+    /// Sets a policy for URLs in `url()`
+    pub fn urls(mut self, urls: UrlPolicy) -> Self {
+        self.urls = urls;
+        self
+    }
+
+    /// Adds a scheme which is allowed in `url()`
+    pub fn url_scheme(mut self, scheme: &str) -> Self {
+        self.urls = self.urls.scheme(scheme);
+        self
+    }
+
+    /// Sets if relative and protocol-relative URLs are allowed in `url()`
+    pub fn allow_relative_urls(mut self, allow_relative_urls: bool) -> Self {
+        self.urls = self
+            .urls
+            .allow_relative(allow_relative_urls)
+            .allow_protocol_relative(allow_relative_urls);
+        self
+    }
+
+    /// Adds an at-rule which is allowed in a stylesheet
+    pub fn at_rule(mut self, name: &str) -> Self {
+        self.at_rules.insert(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Sets a selector which prefixes every selector of a stylesheet
+    pub fn scope(mut self, selector: &str) -> Self {
+        self.scope = Some(selector.to_owned());
+        self
+    }
+
+    fn are_functions_safe(&self, value: &str) -> bool {
+        css::functions(value).into_iter().all(|(name, argument)| {
+            if FORBIDDEN_FUNCTIONS.contains(&name.as_str()) {
+                false
+            } else if name == "url" {
+                self.urls.allows(css::unquote(argument))
+            } else if IMAGE_FUNCTIONS.contains(&name.as_str()) {
+                css::strings(argument)
+                    .into_iter()
+                    .all(|url| self.urls.allows(url))
+            } else {
+                true
+            }
+        })
+    }
+
+    /// Checks if a value is safe regardless of a property it belongs to.
+    pub(crate) fn is_safe_value(&self, value: &str) -> bool {
+        // escapes could hide a function name, the rest could break out of a declaration
+        !value.contains(['\\', '<', '>', '{', '}'])
+            && css::is_balanced(value)
+            && css::functions(value)
+                .iter()
+                .all(|(name, _)| !name.is_empty())
+            && self.are_functions_safe(value)
+    }
+
+    pub(crate) fn is_allowed(&self, declaration: &Declaration) -> bool {
+        if !css::is_valid_property_name(&declaration.name)
+            || FORBIDDEN_PROPERTIES.contains(&declaration.name.as_str())
+            || declaration.value.is_empty()
+        {
+            return false;
+        }
+        match self.properties.get(&declaration.name) {
+            Some(pattern) => {
+                self.is_safe_value(&declaration.value) && pattern.matches(&declaration.value)
+            }
+            None => false,
+        }
+    }
+
+    /// Splits declarations of a `style` attribute into allowed and rejected ones.
+    pub(crate) fn filter_declarations(&self, style: &str) -> (Vec<Declaration>, Vec<Declaration>) {
+        css::parse_declarations(style)
+            .into_iter()
+            .partition(|declaration| self.is_allowed(declaration))
+    }
+
+    /// Sanitizes value of a `style` attribute. Unsafe declarations are removed one by one.
+    pub fn sanitize_style(&self, style: &str) -> String {
+        let (allowed, _) = self.filter_declarations(style);
+        serialize_declarations(&allowed)
+    }
+
+    fn is_safe_selector(&self, selector: &str) -> bool {
+        !selector.is_empty()
+            && !selector.contains(['\\', '<', '{', '}', '@', ';'])
+            && css::is_balanced(selector)
+    }
+
+    fn is_safe_at_rule(&self, name: &str, prelude: &str) -> bool {
+        if !self.at_rules.contains(name)
+            || prelude.contains(['\\', '<', '{', '}', ';'])
+            || !css::is_balanced(prelude)
+            || !self.are_functions_safe(prelude)
+        {
+            return false;
+        }
+        if name == "import" && (prelude.starts_with('"') || prelude.starts_with('\'')) {
+            // `@import "file.css"` is the same as `@import url("file.css")`
+            let url = css::split_top_level(prelude, ' ')[0];
+            return self.urls.allows(css::unquote(url));
+        }
+        true
+    }
+
+    fn scoped(&self, selectors: &str) -> String {
+        match self.scope {
+            Some(ref scope) => css::split_top_level(selectors, ',')
+                .into_iter()
+                .map(|selector| format!("{} {}", scope, selector.trim()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => selectors.to_owned(),
+        }
+    }
+
+    fn filter_rules(&self, input: &str, scoped: bool, rejected: &mut Vec<Rejected>) -> Vec<String> {
+        let mut result = Vec::new();
+        for rule in css::parse_rules(input) {
+            match rule {
+                css::Rule::Qualified { prelude, block } => {
+                    if !self.is_safe_selector(&prelude) {
+                        rejected.push(Rejected::Rule(prelude));
+                        continue;
+                    }
+                    let (allowed, dropped) = self.filter_declarations(&block);
+                    rejected.extend(dropped.into_iter().map(Rejected::Declaration));
+                    if !allowed.is_empty() {
+                        let selectors = if scoped {
+                            self.scoped(&prelude)
+                        } else {
+                            prelude
+                        };
+                        result.push(format!(
+                            "{} {{ {} }}",
+                            selectors,
+                            serialize_declarations(&allowed)
+                        ));
+                    }
+                }
+                css::Rule::At {
+                    name,
+                    prelude,
+                    block,
+                } => {
+                    let head = format!("@{} {}", name, prelude).trim_end().to_owned();
+                    if !self.is_safe_at_rule(&name, &prelude) {
+                        rejected.push(Rejected::Rule(head));
+                        continue;
+                    }
+                    match (name.as_str(), block) {
+                        ("media" | "supports", Some(block)) => {
+                            let rules = self.filter_rules(&block, scoped, rejected);
+                            if !rules.is_empty() {
+                                result.push(format!("{} {{\n{}\n}}", head, rules.join("\n")));
+                            }
+                        }
+                        ("keyframes" | "-webkit-keyframes", Some(block)) => {
+                            // keyframe selectors (`from`, `50%`) are not scoped
+                            let rules = self.filter_rules(&block, false, rejected);
+                            result.push(format!("{} {{\n{}\n}}", head, rules.join("\n")));
+                        }
+                        (_, Some(block)) => {
+                            let (allowed, dropped) = self.filter_declarations(&block);
+                            rejected.extend(dropped.into_iter().map(Rejected::Declaration));
+                            if !allowed.is_empty() {
+                                result.push(format!(
+                                    "{} {{ {} }}",
+                                    head,
+                                    serialize_declarations(&allowed)
+                                ));
+                            }
+                        }
+                        (_, None) => result.push(format!("{};", head)),
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Sanitizes a stylesheet, collecting removed parts into `rejected`.
+    pub(crate) fn filter_stylesheet(
+        &self,
+        stylesheet: &str,
+        rejected: &mut Vec<Rejected>,
+    ) -> String {
+        let stylesheet = css::strip_comments(stylesheet);
+        self.filter_rules(&stylesheet, true, rejected).join("\n")
+    }
+
+    /// Sanitizes a stylesheet. Rules with unsafe selectors and disallowed at-rules are removed,
+    /// unsafe declarations are removed one by one. Selectors are prefixed with a scope, if any.
+    pub fn sanitize_stylesheet(&self, stylesheet: &str) -> String {
+        self.filter_stylesheet(stylesheet, &mut Vec::new())
+    }
+}
+
+pub(crate) fn serialize_declaration(declaration: &Declaration) -> String {
+    if declaration.important {
+        format!("{}: {} !important", declaration.name, declaration.value)
+    } else {
+        format!("{}: {}", declaration.name, declaration.value)
+    }
+}
+
+pub(crate) fn serialize_declarations(declarations: &[Declaration]) -> String {
+    declarations
+        .iter()
+        .map(serialize_declaration)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CssRules;
+    use crate::rules::pattern::Pattern;
+
+    fn rules() -> CssRules {
+        CssRules::new()
+            .property("color", Pattern::any())
+            .property("background", Pattern::any())
+            .property("width", Pattern::any())
+            .property("font-family", Pattern::any())
+            .url_scheme("https")
+            .allow_relative_urls(true)
+    }
+
+    #[test]
+    fn drops_unknown_properties() {
+        assert_eq!(
+            rules().sanitize_style("color: red; position: fixed; behavior: url(a.htc)"),
+            "color: red"
+        );
+    }
+
+    #[test]
+    fn drops_dangerous_values() {
+        let rules = rules();
+        assert_eq!(rules.sanitize_style("width: expression(alert(1))"), "");
+        assert_eq!(rules.sanitize_style("width: EXPRESSION (alert(1))"), "");
+        assert_eq!(rules.sanitize_style("width: \\65 xpression(alert(1))"), "");
+        assert_eq!(
+            rules.sanitize_style("background: url(javascript:alert(1))"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style("background: url(' java\tscript:alert(1)')"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style("background: url(java/**/script:alert(1))"),
+            ""
+        );
+        assert_eq!(rules.sanitize_style("background: url(http://a/b.png)"), "");
+        assert_eq!(rules.sanitize_style("color: red</style><script>"), "");
+    }
+
+    #[test]
+    fn checks_urls_of_image_functions() {
+        let rules = rules();
+        assert_eq!(
+            rules.sanitize_style("background: image-set(\"http://a/b.png\" 1x)"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style("background: -WEBKIT-IMAGE-SET('javascript:alert(1)' 1x)"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style("background: image(\"http://a/b.png\", red)"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style("background: cross-fade(url(a.png), image('http://a/b.png'))"),
+            ""
+        );
+        assert_eq!(
+            rules.sanitize_style(
+                "background: image-set(\"a.png\" type(\"image/png\") 1x, url(https://a/b.png) 2x)"
+            ),
+            "background: image-set(\"a.png\" type(\"image/png\") 1x, url(https://a/b.png) 2x)"
+        );
+    }
+
+    #[test]
+    fn sanitizes_stylesheet() {
+        let rules = rules()
+            .at_rule("media")
+            .at_rule("font-face")
+            .at_rule("import");
+        assert_eq!(
+            rules.sanitize_stylesheet(
+                "@import 'https://a/b.css'; @import \"javascript:alert(1)\"; @charset \"utf-8\";\n\
+                 p > a:not(.x) { color: red; behavior: url(a.htc) }\n\
+                 @media (max-width: 100px) { b { width: 1px } i { position: fixed } }\n\
+                 @font-face { font-family: x; src: url(http://a/x.woff) }\n\
+                 \\62 { color: red } p { color: blue"
+            ),
+            "@import 'https://a/b.css';\n\
+             p > a:not(.x) { color: red }\n\
+             @media (max-width: 100px) {\nb { width: 1px }\n}\n\
+             @font-face { font-family: x }\n\
+             p { color: blue }"
+        );
+    }
+
+    #[test]
+    fn scopes_stylesheet() {
+        let rules = rules().at_rule("media").scope("#post-1");
+        assert_eq!(
+            rules.sanitize_stylesheet("p, a b { color: red } @media print { * { color: red } }"),
+            "#post-1 p, #post-1 a b { color: red }\n@media print {\n#post-1 * { color: red }\n}"
+        );
+    }
+
+    #[test]
+    fn keeps_safe_values() {
+        let rules = rules();
+        assert_eq!(
+            rules.sanitize_style(
+                "background: url(\"https://a/b.png\") no-repeat;width:10px!important"
+            ),
+            "background: url(\"https://a/b.png\") no-repeat; width: 10px !important"
+        );
+        assert_eq!(
+            rules.sanitize_style("background: url(img/b.png)"),
+            "background: url(img/b.png)"
+        );
+        assert_eq!(
+            rules.sanitize_style("font-family: 'Open Sans', serif"),
+            "font-family: 'Open Sans', serif"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Limits of resources which sanitization of a single document may use.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::sanitize_str;
+//! use sanitize_html::errors::{Limit, SanitizeError};
+//! use sanitize_html::rules::limits::{Limits, OnLimit};
+//! use sanitize_html::rules::{Element, Rules};
+//!
+//! let rules = Rules::new()
+//!     .element(Element::new("div"))
+//!     .limits(Limits::new().max_depth(2));
+//! assert!(matches!(
+//!     sanitize_str(&rules, "<div><div><div>deep</div></div></div>"),
+//!     Err(SanitizeError::LimitExceeded { limit: Limit::Depth, max: 2 })
+//! ));
+//!
+//! let rules = Rules::new()
+//!     .element(Element::new("div"))
+//!     .limits(Limits::new().max_depth(2).on_exceed(OnLimit::Flatten));
+//! assert_eq!(
+//!     sanitize_str(&rules, "<div><div><div>deep</div></div></div>").unwrap(),
+//!     "<div><div>deep</div></div>"
+//! );
+//! ```
+
+/// What to do with content which exceeds a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OnLimit {
+    /// Sanitization fails with [`SanitizeError::LimitExceeded`](crate::errors::SanitizeError::LimitExceeded).
+    #[default]
+    Fail,
+    /// Excess content is removed: input is cut, elements nested too deep are removed
+    /// with their content, nodes after the limit and excess attributes are dropped.
+    Truncate,
+    /// The same as `Truncate`, but elements nested too deep are replaced with their text.
+    Flatten,
+}
+
+/// structure to describe resource limits. Limits which are not set are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Limits {
+    /// Maximum size of an input in bytes.
+    pub max_input_bytes: Option<usize>,
+    /// Maximum nesting depth of elements. `html` and `body` are not counted.
+    ///
+    /// Elements are nested the way a browser nests them, so e.g. list items without end tags
+    /// are siblings. [`sanitize_stream`](crate::sanitize_stream) does not count elements
+    /// which a browser inserts without a tag, such as `tbody` of a table.
+    pub max_depth: Option<usize>,
+    /// Maximum number of nodes (elements, texts and comments) in an input.
+    pub max_nodes: Option<usize>,
+    /// Maximum number of attributes of an element.
+    pub max_attributes: Option<usize>,
+    /// What to do with content which exceeds a limit.
+    pub on_exceed: OnLimit,
+}
+
+impl Limits {
+    /// Creates limits which allow anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets maximum size of an input in bytes
+    pub fn max_input_bytes(mut self, max: usize) -> Self {
+        self.max_input_bytes = Some(max);
+        self
+    }
+
+    /// Sets maximum nesting depth of elements
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Sets maximum number of nodes
+    pub fn max_nodes(mut self, max: usize) -> Self {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// Sets maximum number of attributes of an element
+    pub fn max_attributes(mut self, max: usize) -> Self {
+        self.max_attributes = Some(max);
+        self
+    }
+
+    /// Sets what to do with content which exceeds a limit
+    pub fn on_exceed(mut self, on_exceed: OnLimit) -> Self {
+        self.on_exceed = on_exceed;
+        self
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_input_bytes.is_none()
+            && self.max_depth.is_none()
+            && self.max_nodes.is_none()
+            && self.max_attributes.is_none()
+    }
+
+    /// Replaces limits which `other` sets. If it sets any, its `on_exceed` is used too.
+    pub(crate) fn extend(self, other: &Limits) -> Self {
+        Limits {
+            max_input_bytes: other.max_input_bytes.or(self.max_input_bytes),
+            max_depth: other.max_depth.or(self.max_depth),
+            max_nodes: other.max_nodes.or(self.max_nodes),
+            max_attributes: other.max_attributes.or(self.max_attributes),
+            on_exceed: if other.is_unlimited() {
+                self.on_exceed
+            } else {
+                other.on_exceed
+            },
+        }
+    }
+}
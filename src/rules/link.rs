@@ -0,0 +1,132 @@
+//! Rules for `rel` and `target` attributes of links.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::sanitize_str;
+//! use sanitize_html::rules::link::LinkPolicy;
+//! use sanitize_html::rules::pattern::Pattern;
+//! use sanitize_html::rules::{Element, Rules};
+//!
+//! let rules = Rules::new()
+//!     .element(Element::new("a").attribute("href", Pattern::any()))
+//!     .link_policy(
+//!         LinkPolicy::user_content()
+//!             .allow_rel("author")
+//!             .target_blank(true)
+//!             .internal_host("example.com"),
+//!     );
+//!
+//! assert_eq!(
+//!     sanitize_str(&rules, "<a href=\"https://other.com/\" rel=\"author opener\">a</a>").unwrap(),
+//!     "<a href=\"https://other.com/\" rel=\"author nofollow ugc noopener\" target=\"_blank\">a</a>"
+//! );
+//! assert_eq!(
+//!     sanitize_str(&rules, "<a href=\"https://example.com/about\">b</a>").unwrap(),
+//!     "<a href=\"https://example.com/about\" rel=\"nofollow ugc noopener\">b</a>"
+//! );
+//! ```
+
+use super::url;
+use std::collections::HashSet;
+
+/// Elements which a link policy applies to.
+const LINK_ELEMENTS: &[&str] = &["a", "area"];
+
+/// structure to describe `rel` and `target` attributes of links
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct LinkPolicy {
+    /// Tokens which are added to `rel` of every link, in order.
+    pub rel: Vec<String>,
+    /// Tokens of `rel` which an author may use, in lower case. Other tokens are removed.
+    pub allowed_rel: HashSet<String>,
+    /// Determines if links to external hosts get `target="_blank"`.
+    pub target_blank: bool,
+    /// Hosts which are not external. Relative URLs are never external.
+    pub internal_hosts: HashSet<String>,
+}
+
+impl LinkPolicy {
+    /// Creates a policy which removes `rel` of links.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy for links in user generated content, which adds
+    /// `nofollow`, `ugc` and `noopener` to `rel`.
+    pub fn user_content() -> Self {
+        Self::new().rel("nofollow").rel("ugc").rel("noopener")
+    }
+
+    /// Adds a token to `rel` of every link
+    pub fn rel(mut self, token: &str) -> Self {
+        self.rel.push(token.to_ascii_lowercase());
+        self
+    }
+
+    /// Adds a token of `rel` which an author may use
+    pub fn allow_rel(mut self, token: &str) -> Self {
+        self.allowed_rel.insert(token.to_ascii_lowercase());
+        self
+    }
+
+    /// Sets if links to external hosts get `target="_blank"`
+    pub fn target_blank(mut self, target_blank: bool) -> Self {
+        self.target_blank = target_blank;
+        self
+    }
+
+    /// Adds a host which is not external
+    pub fn internal_host(mut self, host: &str) -> Self {
+        self.internal_hosts.insert(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Checks if a policy applies to an element.
+    pub fn applies_to(&self, element_name: &str) -> bool {
+        LINK_ELEMENTS.contains(&element_name)
+    }
+
+    /// Builds `rel` of a link from a value an author wrote: allowed tokens of the author
+    /// followed by added tokens, without duplicates. Returns `None` if no tokens are left.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::link::LinkPolicy;
+    ///
+    /// let policy = LinkPolicy::user_content().allow_rel("me");
+    /// assert_eq!(policy.filter_rel("ME nofollow tag").as_deref(), Some("ME nofollow ugc noopener"));
+    /// assert_eq!(LinkPolicy::new().filter_rel("nofollow"), None);
+    /// ```
+    pub fn filter_rel(&self, value: &str) -> Option<String> {
+        let mut seen = HashSet::new();
+        let tokens: Vec<&str> = value
+            .split_ascii_whitespace()
+            .filter(|token| self.allowed_rel.contains(&token.to_ascii_lowercase()))
+            .chain(self.rel.iter().map(String::as_str))
+            .filter(|token| seen.insert(token.to_ascii_lowercase()))
+            .collect();
+        (!tokens.is_empty()).then(|| tokens.join(" "))
+    }
+
+    /// Checks if a URL leads to an external host.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::link::LinkPolicy;
+    ///
+    /// let policy = LinkPolicy::new().internal_host("example.com");
+    /// assert!(policy.is_external("https://other.com/"));
+    /// assert!(policy.is_external("//other.com/"));
+    /// assert!(!policy.is_external("https://EXAMPLE.com./"));
+    /// assert!(!policy.is_external("/about"));
+    /// assert!(!policy.is_external("mailto:user@other.com"));
+    /// ```
+    pub fn is_external(&self, href: &str) -> bool {
+        url::host(href).is_some_and(|host| !self.internal_hosts.contains(&host))
+    }
+}
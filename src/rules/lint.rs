@@ -0,0 +1,491 @@
+//! Checks of rules for conflicts and dangerous allowances.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::rules::lint::{Category, Lint};
+//! use sanitize_html::rules::pattern::Pattern;
+//! use sanitize_html::rules::{Element, Rules};
+//!
+//! let rules = Rules::new()
+//!     .element(Element::new("a").attribute("href", Pattern::any()))
+//!     .delete("a");
+//!
+//! assert_eq!(
+//!     rules.lint(),
+//!     vec![
+//!         Lint::Conflict {
+//!             element: "a".to_owned(),
+//!             categories: vec![Category::Allowed, Category::Deleted],
+//!         },
+//!         Lint::UnrestrictedUrl {
+//!             element: Some("a".to_owned()),
+//!             attribute: "href".to_owned(),
+//!         },
+//!     ]
+//! );
+//! assert_eq!(
+//!     rules.lint()[0].to_string(),
+//!     "element <a> is allowed, deleted; only the first applies"
+//! );
+//! ```
+
+use super::pattern::AttributeRule;
+use super::url::URL_ATTRIBUTES;
+use super::{MandatoryAttribute, Rename, Rules, wildcard_matches};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Elements of HTML, including obsolete ones, and roots of SVG and MathML.
+const KNOWN_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "acronym",
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "audio",
+    "b",
+    "base",
+    "basefont",
+    "bdi",
+    "bdo",
+    "bgsound",
+    "big",
+    "blink",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "canvas",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "data",
+    "datalist",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "font",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "i",
+    "iframe",
+    "image",
+    "img",
+    "input",
+    "ins",
+    "isindex",
+    "kbd",
+    "keygen",
+    "label",
+    "legend",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "map",
+    "mark",
+    "marquee",
+    "math",
+    "menu",
+    "menuitem",
+    "meta",
+    "meter",
+    "multicol",
+    "nav",
+    "nextid",
+    "nobr",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "optgroup",
+    "option",
+    "output",
+    "p",
+    "param",
+    "picture",
+    "plaintext",
+    "pre",
+    "progress",
+    "q",
+    "rb",
+    "rp",
+    "rt",
+    "rtc",
+    "ruby",
+    "s",
+    "samp",
+    "script",
+    "search",
+    "section",
+    "select",
+    "slot",
+    "small",
+    "source",
+    "spacer",
+    "span",
+    "strike",
+    "strong",
+    "style",
+    "sub",
+    "summary",
+    "sup",
+    "svg",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "title",
+    "tr",
+    "track",
+    "tt",
+    "u",
+    "ul",
+    "var",
+    "video",
+    "wbr",
+    "xmp",
+];
+
+/// Attribute names used to probe wildcards for event handlers.
+const EVENT_HANDLERS: &[&str] = &["onclick", "onerror", "onload", "onmouseover"];
+
+/// Value used to probe rules of URL attributes.
+const SCRIPT_URL: &str = "javascript:alert(1)";
+
+/// Checks if a name is an HTML element or a custom element.
+fn is_known_element(name: &str) -> bool {
+    KNOWN_ELEMENTS.binary_search(&name).is_ok()
+        || (name.contains('-') && name.starts_with(|c: char| c.is_ascii_lowercase()))
+}
+
+fn is_event_handler(attribute: &str) -> bool {
+    attribute.len() > 2 && attribute[..2].eq_ignore_ascii_case("on")
+}
+
+/// Checks if a rule keeps a `javascript:` URL.
+fn accepts_script_url(rule: &AttributeRule) -> bool {
+    rule.apply(SCRIPT_URL).is_some_and(|value| {
+        value
+            .trim_start()
+            .get(..11)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("javascript:"))
+    })
+}
+
+/// How rules handle an element. Categories are listed in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    /// Element is allowed.
+    Allowed,
+    /// Element is removed together with its children.
+    Deleted,
+    /// Element is replaced by spaces.
+    Spaced,
+    /// Element is renamed.
+    Renamed,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::Allowed => "allowed",
+            Category::Deleted => "deleted",
+            Category::Spaced => "replaced with spaces",
+            Category::Renamed => "renamed",
+        })
+    }
+}
+
+/// Problem found in rules
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Lint {
+    /// Element is listed in more than one category, so only the first one applies.
+    Conflict {
+        /// name of an element
+        element: String,
+        /// categories the element is listed in, in order of precedence
+        categories: Vec<Category>,
+    },
+    /// Element is renamed to one which is not allowed.
+    RenameToDisallowed {
+        /// original name of an element
+        from: String,
+        /// new name of an element
+        to: String,
+    },
+    /// Element is renamed to one which is not allowed but renamed too.
+    /// Renames are not applied twice.
+    RenameChain {
+        /// original name of an element
+        from: String,
+        /// new name of an element
+        to: String,
+        /// name the new name is renamed to
+        next: String,
+    },
+    /// Value of a mandatory attribute is rejected by a rule for this attribute.
+    MandatoryRejected {
+        /// name of an element
+        element: String,
+        /// name of an attribute
+        attribute: String,
+        /// mandatory value
+        value: String,
+    },
+    /// Event handler attributes are allowed, so a document can run scripts.
+    EventHandler {
+        /// name of an element, `None` for a global attribute
+        element: Option<String>,
+        /// attribute name or wildcard of a rule
+        attribute: String,
+    },
+    /// URL attribute accepts `javascript:` URLs.
+    UnrestrictedUrl {
+        /// name of an element, `None` for a global attribute
+        element: Option<String>,
+        /// attribute name or wildcard of a rule
+        attribute: String,
+    },
+    /// Rules mention a name which is not an HTML element.
+    UnknownElement {
+        /// name of an element
+        name: String,
+    },
+}
+
+/// Describes where an attribute rule is defined.
+struct Scope<'a>(&'a Option<String>);
+
+impl fmt::Display for Scope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(element) => write!(f, "on <{}>", element),
+            None => f.write_str("on every element"),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::Conflict {
+                element,
+                categories,
+            } => {
+                write!(f, "element <{}> is ", element)?;
+                for (i, category) in categories.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", category)?;
+                }
+                f.write_str("; only the first applies")
+            }
+            Lint::RenameToDisallowed { from, to } => write!(
+                f,
+                "element <{}> is renamed to <{}>, which is not allowed",
+                from, to
+            ),
+            Lint::RenameChain { from, to, next } => write!(
+                f,
+                "element <{}> is renamed to <{}>, which is renamed to <{}>; renames are not applied twice",
+                from, to, next
+            ),
+            Lint::MandatoryRejected {
+                element,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "mandatory attribute {}=\"{}\" of <{}> does not match its rule",
+                attribute, value, element
+            ),
+            Lint::EventHandler { element, attribute } => write!(
+                f,
+                "event handler attribute {} is allowed {}",
+                attribute,
+                Scope(element)
+            ),
+            Lint::UnrestrictedUrl { element, attribute } => write!(
+                f,
+                "URL attribute {} {} accepts javascript: URLs",
+                attribute,
+                Scope(element)
+            ),
+            Lint::UnknownElement { name } => write!(f, "<{}> is not an HTML element", name),
+        }
+    }
+}
+
+/// Checks attribute rules defined in one place.
+fn lint_attributes<'r>(
+    lints: &mut Vec<Lint>,
+    element: Option<&str>,
+    attributes: impl Iterator<Item = (&'r String, &'r AttributeRule)>,
+    wildcard_attributes: &[(String, AttributeRule)],
+) {
+    let element = element.map(str::to_owned);
+    for (attribute, rule) in attributes {
+        if is_event_handler(attribute) {
+            lints.push(Lint::EventHandler {
+                element: element.clone(),
+                attribute: attribute.clone(),
+            });
+        }
+        if URL_ATTRIBUTES.contains(&attribute.as_str()) && accepts_script_url(rule) {
+            lints.push(Lint::UnrestrictedUrl {
+                element: element.clone(),
+                attribute: attribute.clone(),
+            });
+        }
+    }
+    for (wildcard, rule) in wildcard_attributes {
+        if EVENT_HANDLERS
+            .iter()
+            .any(|attribute| wildcard_matches(wildcard, attribute))
+        {
+            lints.push(Lint::EventHandler {
+                element: element.clone(),
+                attribute: wildcard.clone(),
+            });
+        }
+        if URL_ATTRIBUTES
+            .iter()
+            .any(|attribute| wildcard_matches(wildcard, attribute))
+            && accepts_script_url(rule)
+        {
+            lints.push(Lint::UnrestrictedUrl {
+                element: element.clone(),
+                attribute: wildcard.clone(),
+            });
+        }
+    }
+}
+
+pub(crate) fn lint(rules: &Rules) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    let mut categories: HashMap<&str, Vec<Category>> = HashMap::new();
+    let names = [
+        (
+            Category::Allowed,
+            rules.allowed_elements.keys().collect::<Vec<_>>(),
+        ),
+        (Category::Deleted, rules.delete_elements.iter().collect()),
+        (Category::Spaced, rules.space_elements.iter().collect()),
+        (Category::Renamed, rules.rename_elements.keys().collect()),
+    ];
+    for (category, names) in names {
+        for name in names {
+            categories.entry(name).or_default().push(category);
+        }
+    }
+    for (name, categories) in categories {
+        if !is_known_element(name) {
+            lints.push(Lint::UnknownElement {
+                name: name.to_owned(),
+            });
+        }
+        if categories.len() > 1 {
+            lints.push(Lint::Conflict {
+                element: name.to_owned(),
+                categories,
+            });
+        }
+    }
+
+    for (from, Rename { to, .. }) in &rules.rename_elements {
+        if rules.allowed_elements.contains_key(to) {
+            continue;
+        }
+        if let Some(Rename { to: next, .. }) = rules.rename_elements.get(to) {
+            lints.push(Lint::RenameChain {
+                from: from.clone(),
+                to: to.clone(),
+                next: next.clone(),
+            });
+        } else {
+            lints.push(Lint::RenameToDisallowed {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+
+    for element in rules.allowed_elements.values() {
+        for (attribute, MandatoryAttribute { value, .. }) in &element.mandatory_attributes {
+            if let Some(rule) = rules.attribute_rule(element, attribute)
+                && rule.rule.apply(value).is_none()
+            {
+                lints.push(Lint::MandatoryRejected {
+                    element: element.name.clone(),
+                    attribute: attribute.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        lint_attributes(
+            &mut lints,
+            Some(&element.name),
+            element.attributes.iter(),
+            &element.wildcard_attributes,
+        );
+    }
+    lint_attributes(
+        &mut lints,
+        None,
+        rules.global_attributes.iter(),
+        &rules.global_wildcard_attributes,
+    );
+
+    lints.sort();
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KNOWN_ELEMENTS;
+
+    #[test]
+    fn known_elements_are_sorted() {
+        assert!(KNOWN_ELEMENTS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -1,21 +1,197 @@
 //! Structures to define sanitization rules.
 
+pub mod action;
+pub mod class;
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod css;
+pub mod limits;
+pub mod link;
+pub mod lint;
 pub mod pattern;
 pub mod predefined;
+pub mod url;
 
-use self::pattern::Pattern;
+use self::action::{ElementAction, ElementContext, ElementTransformer};
+use self::class::ClassPolicy;
+use self::css::CssRules;
+use self::limits::Limits;
+use self::link::LinkPolicy;
+use self::lint::Lint;
+use self::pattern::AttributeRule;
+use ::url::Url;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Matches attribute name against a wildcard, where `*` matches any sequence of characters
+/// and `?` matches any single character.
+fn wildcard_matches(wildcard: &str, name: &str) -> bool {
+    let wildcard: Vec<char> = wildcard.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut w, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match wildcard.get(w) {
+            Some('*') => {
+                backtrack = Some((w, n));
+                w += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                w += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_w, star_n)) => {
+                    backtrack = Some((star_w, star_n + 1));
+                    w = star_w + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    wildcard[w..].iter().all(|&c| c == '*')
+}
+
+fn find_wildcard<'r>(
+    wildcards: &'r [(String, AttributeRule)],
+    name: &str,
+) -> Option<&'r (String, AttributeRule)> {
+    wildcards
+        .iter()
+        .find(|(wildcard, _)| wildcard_matches(wildcard, name))
+}
+
+/// Puts clones of `first` before `rest`.
+fn prepend<T: Clone>(first: &[T], rest: Vec<T>) -> Vec<T> {
+    first.iter().cloned().chain(rest).collect()
+}
+
+/// Where an attribute rule is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleScope {
+    /// Rule of an element
+    Element,
+    /// Rule shared by all elements
+    Global,
+}
+
+/// Attribute rule which applies to an attribute
+#[derive(Clone, Copy)]
+pub struct AttributeMatch<'r> {
+    /// Where the rule is defined
+    pub scope: RuleScope,
+    /// Attribute name or wildcard of the rule
+    pub key: &'r str,
+    /// Rule for a value
+    pub rule: &'r AttributeRule,
+}
+
+fn find_attribute_rule<'r>(
+    scope: RuleScope,
+    attributes: &'r HashMap<String, AttributeRule>,
+    wildcard_attributes: &'r [(String, AttributeRule)],
+    attribute: &str,
+) -> Option<AttributeMatch<'r>> {
+    if let Some((key, rule)) = attributes.get_key_value(attribute) {
+        return Some(AttributeMatch { scope, key, rule });
+    }
+    find_wildcard(wildcard_attributes, attribute).map(|(key, rule)| AttributeMatch {
+        scope,
+        key,
+        rule,
+    })
+}
+
+/// How a mandatory attribute is combined with an allowed value from a document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MandatoryMode {
+    /// Mandatory value replaces a value from a document.
+    #[default]
+    Override,
+    /// Tokens of a mandatory value which are missing are appended to a value from a document,
+    /// e.g. for `rel` or `class`. Tokens are compared ignoring case of ASCII letters.
+    AppendTokens,
+    /// Mandatory value is used only if a document has no allowed value.
+    SetIfMissing,
+}
+
+/// Value of a mandatory attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MandatoryAttribute {
+    /// Mandatory value
+    pub value: String,
+    /// How the value is combined with a value from a document
+    pub mode: MandatoryMode,
+}
+
+impl MandatoryAttribute {
+    /// Combines a mandatory value with an allowed value from a document.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::{MandatoryAttribute, MandatoryMode};
+    ///
+    /// let rel = MandatoryAttribute {
+    ///     value: "nofollow noopener".to_owned(),
+    ///     mode: MandatoryMode::AppendTokens,
+    /// };
+    /// assert_eq!(rel.apply(Some("me NOFOLLOW")), "me NOFOLLOW noopener");
+    /// assert_eq!(rel.apply(None), "nofollow noopener");
+    /// ```
+    pub fn apply(&self, value: Option<&str>) -> String {
+        let Some(value) = value else {
+            return self.value.clone();
+        };
+        match self.mode {
+            MandatoryMode::Override => self.value.clone(),
+            MandatoryMode::SetIfMissing => value.to_owned(),
+            MandatoryMode::AppendTokens => {
+                let mut tokens: Vec<&str> = value.split_ascii_whitespace().collect();
+                let len = tokens.len();
+                for token in self.value.split_ascii_whitespace() {
+                    if !tokens.iter().any(|t| t.eq_ignore_ascii_case(token)) {
+                        tokens.push(token);
+                    }
+                }
+                if tokens.len() == len {
+                    value.to_owned()
+                } else {
+                    tokens.join(" ")
+                }
+            }
+        }
+    }
+}
 
 /// structure to describe HTML element
+#[derive(Clone)]
 pub struct Element {
     /// name of an element
     pub name: String,
     /// List of allowed attributes
-    pub attributes: HashMap<String, Pattern>,
+    pub attributes: HashMap<String, AttributeRule>,
+    /// List of allowed attributes given by wildcards (e.g. `data-*`), in order of precedence.
+    /// Exact attribute names take precedence over wildcards.
+    pub wildcard_attributes: Vec<(String, AttributeRule)>,
     /// List of mandatory atributes and their values.
-    /// These attributes will be forcibly added to element.
-    pub mandatory_attributes: HashMap<String, String>,
+    /// These attributes will be forcibly added to element, combined with allowed values
+    /// from a document according to their modes.
+    pub mandatory_attributes: HashMap<String, MandatoryAttribute>,
+    /// Rules to sanitize `style` attribute.
+    /// If set, they take precedence over an attribute rule for `style`.
+    pub style: Option<CssRules>,
+    /// Classes allowed in `class` attribute.
+    /// If set, it takes precedence over an attribute rule for `class`.
+    pub class: Option<ClassPolicy>,
 }
 
 impl Element {
@@ -24,45 +200,323 @@ impl Element {
         Self {
             name: name.to_owned(),
             attributes: HashMap::new(),
+            wildcard_attributes: Vec::new(),
             mandatory_attributes: HashMap::new(),
+            style: None,
+            class: None,
         }
     }
 
-    /// Adds an attribute
-    pub fn attribute(mut self, attribute: &str, pattern: Pattern) -> Self {
-        self.attributes.insert(attribute.to_owned(), pattern);
+    /// Adds an attribute with a pattern or a transformer for its value
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::pattern::AttributeRule;
+    ///
+    /// let rules = Rules::new().element(Element::new("ol").attribute(
+    ///     "type",
+    ///     AttributeRule::transform(|value| {
+    ///         let value = value.trim().to_ascii_lowercase();
+    ///         matches!(value.as_str(), "1" | "a" | "i").then_some(value)
+    ///     }),
+    /// ));
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<ol type=\" A \"></ol><ol type=\"disc\"></ol>").unwrap(),
+    ///     "<ol type=\"a\"></ol><ol></ol>"
+    /// );
+    /// ```
+    pub fn attribute(mut self, attribute: &str, rule: impl Into<AttributeRule>) -> Self {
+        self.attributes.insert(attribute.to_owned(), rule.into());
+        self
+    }
+
+    /// Adds attributes matching a wildcard, where `*` matches any sequence of characters
+    /// and `?` matches any single character
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::Element;
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let element = Element::new("div").attribute_wildcard("aria-*", Pattern::any());
+    /// assert!(element.is_valid("aria-label", "Close"));
+    /// assert!(!element.is_valid("role", "button"));
+    /// ```
+    pub fn attribute_wildcard(mut self, wildcard: &str, rule: impl Into<AttributeRule>) -> Self {
+        self.wildcard_attributes
+            .push((wildcard.to_owned(), rule.into()));
+        self
+    }
+
+    /// Adds attributes which names start with a prefix
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::Element;
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let element = Element::new("div").attribute_prefix("data-", Pattern::any());
+    /// assert!(element.is_valid("data-id", "42"));
+    /// ```
+    pub fn attribute_prefix(self, prefix: &str, rule: impl Into<AttributeRule>) -> Self {
+        let wildcard = format!("{}*", prefix);
+        self.attribute_wildcard(&wildcard, rule)
+    }
+
+    /// Adds mandatory attribute, which value replaces a value from a document
+    pub fn mandatory_attribute(self, attribute: &str, value: &str) -> Self {
+        self.mandatory_attribute_mode(attribute, value, MandatoryMode::Override)
+    }
+
+    /// Adds mandatory attribute, which value is combined with a value from a document
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, MandatoryMode, Rules};
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let rules = Rules::new().element(
+    ///     Element::new("a")
+    ///         .attribute("rel", Pattern::any())
+    ///         .attribute("title", Pattern::any())
+    ///         .mandatory_attribute_mode("rel", "nofollow", MandatoryMode::AppendTokens)
+    ///         .mandatory_attribute_mode("title", "link", MandatoryMode::SetIfMissing),
+    /// );
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<a rel=\"me\" title=\"home\">a</a><a>b</a>").unwrap(),
+    ///     "<a rel=\"me nofollow\" title=\"home\">a</a><a rel=\"nofollow\" title=\"link\">b</a>"
+    /// );
+    /// ```
+    pub fn mandatory_attribute_mode(
+        mut self,
+        attribute: &str,
+        value: &str,
+        mode: MandatoryMode,
+    ) -> Self {
+        self.mandatory_attributes.insert(
+            attribute.to_owned(),
+            MandatoryAttribute {
+                value: value.to_owned(),
+                mode,
+            },
+        );
         self
     }
 
-    /// Adds mandatory attribute
-    pub fn mandatory_attribute(mut self, attribute: &str, value: &str) -> Self {
-        self.mandatory_attributes
-            .insert(attribute.to_owned(), value.to_owned());
+    /// Allows `style` attribute, which is sanitized declaration by declaration
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::css::CssRules;
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let rules = Rules::new()
+    ///     .element(Element::new("p").style(CssRules::new().property("color", Pattern::any())));
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<p style=\"color: red; position: fixed\">a</p>").unwrap(),
+    ///     "<p style=\"color: red\">a</p>"
+    /// );
+    /// ```
+    pub fn style(mut self, rules: CssRules) -> Self {
+        self.style = Some(rules);
         self
     }
 
-    /// Checks if attribute is valid
+    /// Allows `class` attribute, which is filtered class by class
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::class::ClassPolicy;
+    /// use regex::Regex;
+    ///
+    /// let rules = Rules::new().element(
+    ///     Element::new("code")
+    ///         .class_policy(ClassPolicy::new().pattern(Regex::new("^language-[a-z]+$").unwrap())),
+    /// );
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<code class=\"huge language-rust\">a</code>").unwrap(),
+    ///     "<code class=\"language-rust\">a</code>"
+    /// );
+    /// ```
+    pub fn class_policy(mut self, policy: ClassPolicy) -> Self {
+        self.class = Some(policy);
+        self
+    }
+
+    /// Adds rules of another descriptor of the same element.
+    ///
+    /// Attribute rules, mandatory attributes, style rules and class policy of `other`
+    /// override these ones. Wildcard attributes of `other` take precedence over these ones.
+    pub fn extend(mut self, other: &Element) -> Self {
+        for (attribute, rule) in &other.attributes {
+            self.attributes.insert(attribute.clone(), rule.clone());
+        }
+        self.wildcard_attributes = prepend(&other.wildcard_attributes, self.wildcard_attributes);
+        for (attribute, mandatory) in &other.mandatory_attributes {
+            self.mandatory_attributes
+                .insert(attribute.clone(), mandatory.clone());
+        }
+        if other.style.is_some() {
+            self.style = other.style.clone();
+        }
+        if other.class.is_some() {
+            self.class = other.class.clone();
+        }
+        self
+    }
+
+    /// Removes rules for an attribute: its exact name or a wildcard, and a mandatory value.
+    /// Removing `style` or `class` also removes style rules or a class policy.
+    ///
+    /// Other wildcards can still match the attribute.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::Element;
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let element = Element::new("div")
+    ///     .attribute("title", Pattern::any())
+    ///     .attribute_prefix("data-", Pattern::any())
+    ///     .without_attribute("data-*");
+    /// assert!(element.is_valid("title", "a"));
+    /// assert!(!element.is_valid("data-id", "1"));
+    /// ```
+    pub fn without_attribute(mut self, attribute: &str) -> Self {
+        self.remove_attribute(attribute);
+        self
+    }
+
+    fn remove_attribute(&mut self, attribute: &str) {
+        self.attributes.remove(attribute);
+        self.wildcard_attributes
+            .retain(|(wildcard, _)| wildcard != attribute);
+        self.mandatory_attributes.remove(attribute);
+        match attribute {
+            "style" => self.style = None,
+            "class" => self.class = None,
+            _ => {}
+        }
+    }
+
+    /// Finds a rule for an attribute. Exact names take precedence over wildcards.
+    pub fn attribute_rule(&self, attribute: &str) -> Option<AttributeMatch<'_>> {
+        find_attribute_rule(
+            RuleScope::Element,
+            &self.attributes,
+            &self.wildcard_attributes,
+            attribute,
+        )
+    }
+
+    /// Checks if attribute is valid by rules of this element only.
+    ///
+    /// Global attributes of [`Rules`] are not consulted, use [`Rules::is_valid_attribute`]
+    /// to check an attribute the way sanitization does.
     pub fn is_valid(&self, attribute: &str, value: &str) -> bool {
-        match self.attributes.get(attribute) {
+        match self.attribute_rule(attribute) {
             None => false,
-            Some(pattern) => pattern.matches(value),
+            Some(rule) => rule.rule.apply(value).is_some(),
         }
     }
 }
 
+/// Function which maps attributes of a renamed element to attributes of a new element.
+pub type AttributeMapper = Arc<dyn Fn(&[(String, String)]) -> Vec<(String, String)> + Sync + Send>;
+
+/// What happens to attributes of a renamed element
+#[derive(Clone, Default)]
+pub enum RenameAttributes {
+    /// Attributes are removed. Mandatory attributes of the new element are added.
+    #[default]
+    Drop,
+    /// Attributes are checked by rules of the new element, as if the new element
+    /// was in a document. If it is not an allowed element, attributes are removed.
+    Keep,
+    /// Attributes are mapped by a function to new ones, which are checked
+    /// the way [`RenameAttributes::Keep`] does it.
+    Map(AttributeMapper),
+}
+
+impl fmt::Debug for RenameAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameAttributes::Drop => f.write_str("Drop"),
+            RenameAttributes::Keep => f.write_str("Keep"),
+            RenameAttributes::Map(_) => f.write_str("Map(..)"),
+        }
+    }
+}
+
+impl PartialEq for RenameAttributes {
+    /// Mapping functions are equal if they are the same function.
+    fn eq(&self, other: &RenameAttributes) -> bool {
+        match (self, other) {
+            (RenameAttributes::Drop, RenameAttributes::Drop) => true,
+            (RenameAttributes::Keep, RenameAttributes::Keep) => true,
+            (RenameAttributes::Map(a), RenameAttributes::Map(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RenameAttributes {}
+
+/// Rule to rename an element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    /// new name of an element
+    pub to: String,
+    /// what happens to attributes of an element
+    pub attributes: RenameAttributes,
+}
+
 /// structure to describe sanitization rules
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Rules {
     /// Determines if comments are kept of stripped out of a document.
     pub allow_comments: bool,
+    /// Determines if a document which needs sanitization is rejected instead of being changed.
+    pub strict: bool,
     /// Allowed elements.
     pub allowed_elements: HashMap<String, Element>,
+    /// Attributes allowed on every allowed element.
+    /// Attribute rules of an element override these ones.
+    pub global_attributes: HashMap<String, AttributeRule>,
+    /// Attributes given by wildcards which are allowed on every allowed element.
+    pub global_wildcard_attributes: Vec<(String, AttributeRule)>,
     /// Elements which will be removed together with their children.
     pub delete_elements: HashSet<String>,
     /// Elements which will be replaced by spaces (Their children will be processed recursively).
     pub space_elements: HashSet<String>,
     /// Elements which will be renamed.
-    pub rename_elements: HashMap<String, String>,
+    pub rename_elements: HashMap<String, Rename>,
+    /// Rules to sanitize content of `style` elements.
+    /// If not set and `style` is an allowed element, [`predefined::STYLESHEET`] is used.
+    pub stylesheet: Option<CssRules>,
+    /// URL which relative URLs of kept attributes are resolved against.
+    pub base_url: Option<Url>,
+    /// Classes allowed in `class` attribute of every allowed element.
+    /// Class policy or `class` attribute rule of an element override this one.
+    pub class_policy: Option<ClassPolicy>,
+    /// Rules for `rel` and `target` attributes of links.
+    /// It takes precedence over attribute rules and mandatory attributes for them.
+    pub link_policy: Option<LinkPolicy>,
+    /// Custom handling of elements, which takes precedence over other element rules.
+    pub transformer: Option<ElementTransformer>,
+    /// Limits of resources sanitization of a document may use.
+    pub limits: Limits,
 }
 
 impl Rules {
@@ -77,12 +531,152 @@ impl Rules {
         self
     }
 
+    /// Sets strict mode.
+    ///
+    /// In strict mode sanitization fails with [`SanitizeError::Policy`](crate::errors::SanitizeError::Policy)
+    /// if a document has anything to remove or change, and returns a clean document unchanged.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     /// Adds a rule for an allowed element
     pub fn element(mut self, element: Element) -> Self {
         self.allowed_elements.insert(element.name.clone(), element);
         self
     }
 
+    /// Adds an attribute which is allowed on every allowed element
+    pub fn global_attribute(mut self, attribute: &str, rule: impl Into<AttributeRule>) -> Self {
+        self.global_attributes
+            .insert(attribute.to_owned(), rule.into());
+        self
+    }
+
+    /// Adds attributes matching a wildcard which are allowed on every allowed element
+    pub fn global_attribute_wildcard(
+        mut self,
+        wildcard: &str,
+        rule: impl Into<AttributeRule>,
+    ) -> Self {
+        self.global_wildcard_attributes
+            .push((wildcard.to_owned(), rule.into()));
+        self
+    }
+
+    /// Adds attributes which names start with a prefix which are allowed on every allowed element
+    pub fn global_attribute_prefix(self, prefix: &str, rule: impl Into<AttributeRule>) -> Self {
+        let wildcard = format!("{}*", prefix);
+        self.global_attribute_wildcard(&wildcard, rule)
+    }
+
+    /// Finds a rule for an attribute of an allowed element.
+    ///
+    /// Rules are looked up in this order: attributes of the element, wildcard attributes of the element,
+    /// global attributes, global wildcard attributes.
+    pub fn attribute_rule<'r>(
+        &'r self,
+        element: &'r Element,
+        attribute: &str,
+    ) -> Option<AttributeMatch<'r>> {
+        element.attribute_rule(attribute).or_else(|| {
+            find_attribute_rule(
+                RuleScope::Global,
+                &self.global_attributes,
+                &self.global_wildcard_attributes,
+                attribute,
+            )
+        })
+    }
+
+    /// Sets classes allowed in `class` attribute of every allowed element
+    pub fn class_policy(mut self, policy: ClassPolicy) -> Self {
+        self.class_policy = Some(policy);
+        self
+    }
+
+    /// Sets rules for `rel` and `target` attributes of links, which are `a` and `area` elements
+    /// with an allowed `href`. See [`link`] for an example.
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.link_policy = Some(policy);
+        self
+    }
+
+    /// Finds a class policy for an allowed element.
+    pub fn class_policy_for<'r>(&'r self, element: &'r Element) -> Option<&'r ClassPolicy> {
+        match element.class {
+            Some(ref policy) => Some(policy),
+            None if element.attribute_rule("class").is_none() => self.class_policy.as_ref(),
+            None => None,
+        }
+    }
+
+    /// Checks if attribute of an allowed element is valid
+    pub fn is_valid_attribute(&self, element: &Element, attribute: &str, value: &str) -> bool {
+        match self.attribute_rule(element, attribute) {
+            None => false,
+            Some(rule) => rule.rule.apply(value).is_some(),
+        }
+    }
+
+    /// Checks rules for conflicts and dangerous allowances. See [`lint`] for an example.
+    ///
+    /// An element listed in several categories is handled by the first of them in this order:
+    /// allowed, deleted, replaced with spaces, renamed.
+    pub fn lint(&self) -> Vec<Lint> {
+        lint::lint(self)
+    }
+
+    /// Sets rules to sanitize content of `style` elements
+    pub fn stylesheet(mut self, rules: CssRules) -> Self {
+        self.stylesheet = Some(rules);
+        self
+    }
+
+    /// Sets a base URL to rewrite relative URLs to absolute ones.
+    ///
+    /// Values of URL attributes like `href` and `src` are resolved after they pass validation.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::pattern::Pattern;
+    /// use sanitize_html::rules::url::Url;
+    ///
+    /// let rules = Rules::new()
+    ///     .element(Element::new("a").attribute("href", Pattern::any()))
+    ///     .base_url(Url::parse("https://example.com/posts/1/").unwrap());
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<a href=\"../2/\">next</a>").unwrap(),
+    ///     "<a href=\"https://example.com/posts/2/\">next</a>"
+    /// );
+    /// ```
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Sets a function which decides what to do with an element.
+    ///
+    /// It is called for every element but `html` and `body`, which are always removed
+    /// keeping their children. If it returns `None`, other rules apply.
+    /// See [`action`] for an example.
+    pub fn transformer<F>(mut self, transformer: F) -> Self
+    where
+        F: Fn(&ElementContext) -> Option<ElementAction> + Sync + Send + 'static,
+    {
+        self.transformer = Some(Arc::new(transformer));
+        self
+    }
+
+    /// Sets resource limits
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Adds a rule to delete an element
     pub fn delete(mut self, element_name: &str) -> Self {
         self.delete_elements.insert(element_name.to_owned());
@@ -95,10 +689,199 @@ impl Rules {
         self
     }
 
-    /// Adds a rule to rename an element
-    pub fn rename(mut self, element_name: &str, to: &str) -> Self {
+    /// Adds a rule to rename an element. Its attributes are removed.
+    pub fn rename(self, element_name: &str, to: &str) -> Self {
+        self.rename_with(element_name, to, RenameAttributes::Drop)
+    }
+
+    /// Adds a rule to rename an element, which keeps attributes allowed by rules of the new element
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let rules = Rules::new()
+    ///     .element(Element::new("strong").attribute("title", Pattern::any()))
+    ///     .rename_keeping_attributes("b", "strong");
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<b title=\"t\" onclick=\"go()\">a</b>").unwrap(),
+    ///     "<strong title=\"t\">a</strong>"
+    /// );
+    /// ```
+    pub fn rename_keeping_attributes(self, element_name: &str, to: &str) -> Self {
+        self.rename_with(element_name, to, RenameAttributes::Keep)
+    }
+
+    /// Adds a rule to rename an element, which maps its attributes to new ones. New attributes
+    /// are kept if rules of the new element allow them.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::css::CssRules;
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let rules = Rules::new()
+    ///     .element(Element::new("span").style(CssRules::new().property("color", Pattern::any())))
+    ///     .rename_mapping_attributes("font", "span", |attributes| {
+    ///         attributes
+    ///             .iter()
+    ///             .filter(|(name, _)| name == "color")
+    ///             .map(|(_, value)| ("style".to_owned(), format!("color: {}", value)))
+    ///             .collect()
+    ///     });
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<font color=\"red\" face=\"serif\">a</font>").unwrap(),
+    ///     "<span style=\"color: red\">a</span>"
+    /// );
+    /// ```
+    pub fn rename_mapping_attributes<F>(self, element_name: &str, to: &str, mapper: F) -> Self
+    where
+        F: Fn(&[(String, String)]) -> Vec<(String, String)> + Sync + Send + 'static,
+    {
+        self.rename_with(element_name, to, RenameAttributes::Map(Arc::new(mapper)))
+    }
+
+    fn rename_with(mut self, element_name: &str, to: &str, attributes: RenameAttributes) -> Self {
+        self.rename_elements.insert(
+            element_name.to_owned(),
+            Rename {
+                to: to.to_owned(),
+                attributes,
+            },
+        );
+        self
+    }
+
+    /// Adds rules of another set to these ones, e.g. to allow more elements than a preset does.
+    ///
+    /// Rules of `other` take precedence:
+    /// - an element mentioned by `other` is handled the way `other` says: it is removed from
+    ///   the allowed, deleted, spaced and renamed elements of this set first. If both sets allow
+    ///   an element, their descriptors are merged by [`Element::extend`];
+    /// - global attributes of `other` override these ones, and its global wildcard attributes
+    ///   take precedence over these ones;
+    /// - stylesheet rules, base URL, class policy, link policy and transformer of `other`
+    ///   replace these ones if they are set;
+    /// - limits which `other` sets replace these ones, together with its `on_exceed`;
+    /// - comments are allowed if either set allows them, and rules are strict if either set is.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::{Element, Rules};
+    /// use sanitize_html::rules::pattern::Pattern;
+    /// use sanitize_html::rules::predefined::RELAXED;
+    /// use sanitize_html::rules::url::UrlPolicy;
+    ///
+    /// let youtube = Rules::new().element(
+    ///     Element::new("iframe").attribute(
+    ///         "src",
+    ///         Pattern::url(UrlPolicy::new().scheme("https").host("www.youtube.com")),
+    ///     ),
+    /// );
+    /// let rules = RELAXED.clone().extend(&youtube).without_element("img");
+    ///
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<iframe src=\"https://www.youtube.com/embed/x\"></iframe><img src=\"a.png\"><b>b</b>").unwrap(),
+    ///     "<iframe src=\"https://www.youtube.com/embed/x\"></iframe><b>b</b>"
+    /// );
+    /// ```
+    pub fn extend(mut self, other: &Rules) -> Self {
+        for (name, element) in &other.allowed_elements {
+            let element = match self.allowed_elements.remove(name) {
+                Some(own) => own.extend(element),
+                None => element.clone(),
+            };
+            self.forget_element(name);
+            self.allowed_elements.insert(name.clone(), element);
+        }
+        for name in &other.delete_elements {
+            self.forget_element(name);
+            self.delete_elements.insert(name.clone());
+        }
+        for name in &other.space_elements {
+            self.forget_element(name);
+            self.space_elements.insert(name.clone());
+        }
+        for (name, rename) in &other.rename_elements {
+            self.forget_element(name);
+            self.rename_elements.insert(name.clone(), rename.clone());
+        }
+        for (attribute, rule) in &other.global_attributes {
+            self.global_attributes
+                .insert(attribute.clone(), rule.clone());
+        }
+        self.global_wildcard_attributes = prepend(
+            &other.global_wildcard_attributes,
+            self.global_wildcard_attributes,
+        );
+        self.allow_comments |= other.allow_comments;
+        self.strict |= other.strict;
+        if other.stylesheet.is_some() {
+            self.stylesheet = other.stylesheet.clone();
+        }
+        if other.base_url.is_some() {
+            self.base_url = other.base_url.clone();
+        }
+        if other.class_policy.is_some() {
+            self.class_policy = other.class_policy.clone();
+        }
+        if other.link_policy.is_some() {
+            self.link_policy = other.link_policy.clone();
+        }
+        if other.transformer.is_some() {
+            self.transformer = other.transformer.clone();
+        }
+        self.limits = self.limits.extend(&other.limits);
+        self
+    }
+
+    /// Removes every rule for an element, so it is removed keeping its children.
+    /// Renames to the element are removed too.
+    pub fn without_element(mut self, element_name: &str) -> Self {
+        self.forget_element(element_name);
         self.rename_elements
-            .insert(element_name.to_owned(), to.to_owned());
+            .retain(|_, rename| rename.to != element_name);
+        self
+    }
+
+    /// Removes rules for an attribute from global attributes and from every allowed element.
+    /// See [`Element::without_attribute`] for details.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::sanitize_str;
+    /// use sanitize_html::rules::predefined::RELAXED;
+    ///
+    /// let rules = RELAXED.clone().without_attribute("title");
+    /// assert_eq!(
+    ///     sanitize_str(&rules, "<abbr title=\"a\" lang=\"en\">b</abbr>").unwrap(),
+    ///     "<abbr lang=\"en\">b</abbr>"
+    /// );
+    /// ```
+    pub fn without_attribute(mut self, attribute: &str) -> Self {
+        self.global_attributes.remove(attribute);
+        self.global_wildcard_attributes
+            .retain(|(wildcard, _)| wildcard != attribute);
+        if attribute == "class" {
+            self.class_policy = None;
+        }
+        for element in self.allowed_elements.values_mut() {
+            element.remove_attribute(attribute);
+        }
         self
     }
+
+    fn forget_element(&mut self, element_name: &str) {
+        self.allowed_elements.remove(element_name);
+        self.delete_elements.remove(element_name);
+        self.space_elements.remove(element_name);
+        self.rename_elements.remove(element_name);
+    }
 }
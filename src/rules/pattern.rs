@@ -14,10 +14,49 @@
 //! assert!(!href.matches(" javascript  : window.location = '//example.com/'")); // Attempt to make XSS
 //! ```
 
+use super::url::UrlPolicy;
 use regex::Regex;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+/// Function which checks a value.
+pub type Predicate = Arc<dyn Fn(&str) -> bool + Sync + Send>;
 
 /// Value pattern
-pub struct Pattern(pub Box<dyn Fn(&str) -> bool + Sync + Send>);
+///
+/// Patterns are plain data, so they can be printed and compared. A pattern is displayed
+/// the way it reads in a report:
+///
+/// ```
+/// use sanitize_html::rules::pattern::Pattern;
+///
+/// let pattern = Pattern::one_of(["auto"]) | Pattern::range(0.0..=100.0) & !Pattern::one_of(["13"]);
+/// assert_eq!(pattern.to_string(), r#"one of "auto" or (number in [0, 100] and not one of "13")"#);
+/// ```
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Pattern {
+    /// Accepts any value
+    Any,
+    /// Accepts a value which matches a regular expression
+    Regex(Regex),
+    /// Accepts one of values, ignoring case of ASCII letters
+    OneOf(Vec<String>),
+    /// Accepts a URL allowed by a policy
+    Url(UrlPolicy),
+    /// Accepts a number between bounds
+    Range(Bound<f64>, Bound<f64>),
+    /// Accepts a value which a pattern does not accept
+    Not(Box<Pattern>),
+    /// Accepts a value which all patterns accept
+    And(Vec<Pattern>),
+    /// Accepts a value which any of patterns accepts
+    Or(Vec<Pattern>),
+    /// Accepts a value for which a function returns `true`
+    Custom(Predicate),
+}
 
 impl Pattern {
     /// Creates pattern which accepts any value.
@@ -32,7 +71,7 @@ impl Pattern {
     /// assert!(pattern.matches("pants"));
     /// ```
     pub fn any() -> Self {
-        Pattern(Box::new(move |_value| true))
+        Pattern::Any
     }
 
     /// Creates pattern which uses regular expression to check a value. Panics
@@ -47,12 +86,192 @@ impl Pattern {
     /// assert!(pattern.matches("pants"));
     /// ```
     pub fn regex(re: Regex) -> Self {
-        Pattern(Box::new(move |value| re.is_match(value)))
+        Pattern::Regex(re)
+    }
+
+    /// Creates pattern which checks a value as a URL.
+    ///
+    /// Value is normalized the way browsers do it before a scheme is checked, so neither
+    /// case, nor control characters, nor whitespace can sneak a forbidden scheme through.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::pattern::Pattern;
+    /// use sanitize_html::rules::url::UrlPolicy;
+    ///
+    /// let pattern = Pattern::url(UrlPolicy::new().scheme("https").allow_relative(true));
+    /// assert!(pattern.matches("https://example.com/"));
+    /// assert!(pattern.matches("images/cat.png"));
+    /// assert!(!pattern.matches("\tjavascript:alert(1)"));
+    /// assert!(!pattern.matches("data:text/html,hello"));
+    /// ```
+    pub fn url(policy: UrlPolicy) -> Self {
+        Pattern::Url(policy)
+    }
+
+    /// Creates pattern which accepts one of listed values. Case of ASCII letters is ignored,
+    /// the way browsers compare enumerated attributes.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let pattern = Pattern::one_of(["left", "right"]);
+    /// assert!(pattern.matches("Left"));
+    /// assert!(!pattern.matches("center"));
+    /// ```
+    pub fn one_of<I>(values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Pattern::OneOf(values.into_iter().map(Into::into).collect())
+    }
+
+    /// Creates pattern which accepts a number in a range. Whitespace around a number is ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let pattern = Pattern::range(1.0..=640.0);
+    /// assert!(pattern.matches(" 320 "));
+    /// assert!(pattern.matches("1.5"));
+    /// assert!(!pattern.matches("0"));
+    /// assert!(!pattern.matches("100%"));
+    /// assert!(!pattern.matches("NaN"));
+    /// ```
+    pub fn range(range: impl RangeBounds<f64>) -> Self {
+        Pattern::Range(range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    /// Creates pattern which uses a function to check a value.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::pattern::Pattern;
+    ///
+    /// let pattern = Pattern::custom(|value| value.len() <= 3);
+    /// assert!(pattern.matches("ant"));
+    /// assert!(!pattern.matches("pants"));
+    /// ```
+    pub fn custom<F>(predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Sync + Send + 'static,
+    {
+        Pattern::Custom(Arc::new(predicate))
     }
 
     /// Checks if a value matches to a pattern.
     pub fn matches(&self, value: &str) -> bool {
-        (self.0)(value)
+        match self {
+            Pattern::Any => true,
+            Pattern::Regex(re) => re.is_match(value),
+            Pattern::OneOf(values) => values
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(value)),
+            Pattern::Url(policy) => policy.allows(value),
+            Pattern::Range(start, end) => value
+                .trim()
+                .parse::<f64>()
+                .is_ok_and(|number| number.is_finite() && (*start, *end).contains(&number)),
+            Pattern::Not(pattern) => !pattern.matches(value),
+            Pattern::And(patterns) => patterns.iter().all(|pattern| pattern.matches(value)),
+            Pattern::Or(patterns) => patterns.iter().any(|pattern| pattern.matches(value)),
+            Pattern::Custom(predicate) => predicate(value),
+        }
+    }
+
+    fn is_compound(&self) -> bool {
+        matches!(self, Pattern::And(_) | Pattern::Or(_))
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Any => f.write_str("Any"),
+            Pattern::Regex(re) => f.debug_tuple("Regex").field(re).finish(),
+            Pattern::OneOf(values) => f.debug_tuple("OneOf").field(values).finish(),
+            Pattern::Url(policy) => f.debug_tuple("Url").field(policy).finish(),
+            Pattern::Range(start, end) => f.debug_tuple("Range").field(start).field(end).finish(),
+            Pattern::Not(pattern) => f.debug_tuple("Not").field(pattern).finish(),
+            Pattern::And(patterns) => f.debug_tuple("And").field(patterns).finish(),
+            Pattern::Or(patterns) => f.debug_tuple("Or").field(patterns).finish(),
+            Pattern::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Writes patterns joined by a word, with compound ones in parentheses.
+fn join(f: &mut fmt::Formatter, patterns: &[Pattern], word: &str) -> fmt::Result {
+    for (i, pattern) in patterns.iter().enumerate() {
+        if i > 0 {
+            write!(f, " {} ", word)?;
+        }
+        if pattern.is_compound() {
+            write!(f, "({})", pattern)?;
+        } else {
+            write!(f, "{}", pattern)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Any => f.write_str("any value"),
+            Pattern::Regex(re) => write!(f, "regex /{}/", re),
+            Pattern::OneOf(values) => {
+                f.write_str("one of ")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:?}", value)?;
+                }
+                Ok(())
+            }
+            Pattern::Url(policy) => write!(f, "{}", policy),
+            Pattern::Range(start, end) => {
+                f.write_str("number in ")?;
+                match start {
+                    Bound::Included(start) => write!(f, "[{}", start)?,
+                    Bound::Excluded(start) => write!(f, "({}", start)?,
+                    Bound::Unbounded => f.write_str("(-inf")?,
+                }
+                match end {
+                    Bound::Included(end) => write!(f, ", {}]", end),
+                    Bound::Excluded(end) => write!(f, ", {})", end),
+                    Bound::Unbounded => f.write_str(", inf)"),
+                }
+            }
+            Pattern::Not(pattern) if pattern.is_compound() => write!(f, "not ({})", pattern),
+            Pattern::Not(pattern) => write!(f, "not {}", pattern),
+            Pattern::And(patterns) if patterns.is_empty() => f.write_str("any value"),
+            Pattern::And(patterns) => join(f, patterns, "and"),
+            Pattern::Or(patterns) if patterns.is_empty() => f.write_str("no value"),
+            Pattern::Or(patterns) => join(f, patterns, "or"),
+            Pattern::Custom(_) => f.write_str("custom function"),
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    /// Compares patterns structurally. Regular expressions are equal if their sources are
+    /// equal. Custom patterns are never equal, as functions can not be compared.
+    fn eq(&self, other: &Pattern) -> bool {
+        match (self, other) {
+            (Pattern::Any, Pattern::Any) => true,
+            (Pattern::Regex(a), Pattern::Regex(b)) => a.as_str() == b.as_str(),
+            (Pattern::OneOf(a), Pattern::OneOf(b)) => a == b,
+            (Pattern::Url(a), Pattern::Url(b)) => a == b,
+            (Pattern::Range(a1, a2), Pattern::Range(b1, b2)) => a1 == b1 && a2 == b2,
+            (Pattern::Not(a), Pattern::Not(b)) => a == b,
+            (Pattern::And(a), Pattern::And(b)) | (Pattern::Or(a), Pattern::Or(b)) => a == b,
+            _ => false,
+        }
     }
 }
 
@@ -71,8 +290,10 @@ impl ::std::ops::Not for Pattern {
     /// assert!(!pattern.matches("pants"));
     /// ```
     fn not(self) -> Self::Output {
-        let cb = self.0;
-        Pattern(Box::new(move |value| !cb(value)))
+        match self {
+            Pattern::Not(pattern) => *pattern,
+            pattern => Pattern::Not(Box::new(pattern)),
+        }
     }
 }
 
@@ -95,9 +316,15 @@ impl ::std::ops::BitAnd for Pattern {
     /// assert!(pattern.matches("pants"));
     /// ```
     fn bitand(self, rhs: Pattern) -> Self::Output {
-        let cb1 = self.0;
-        let cb2 = rhs.0;
-        Pattern(Box::new(move |value| cb1(value) && cb2(value)))
+        let mut patterns = match self {
+            Pattern::And(patterns) => patterns,
+            pattern => vec![pattern],
+        };
+        match rhs {
+            Pattern::And(rhs) => patterns.extend(rhs),
+            rhs => patterns.push(rhs),
+        }
+        Pattern::And(patterns)
     }
 }
 
@@ -120,8 +347,75 @@ impl ::std::ops::BitOr for Pattern {
     /// assert!(!pattern.matches("jar"));
     /// ```
     fn bitor(self, rhs: Pattern) -> Self::Output {
-        let cb1 = self.0;
-        let cb2 = rhs.0;
-        Pattern(Box::new(move |value| cb1(value) || cb2(value)))
+        let mut patterns = match self {
+            Pattern::Or(patterns) => patterns,
+            pattern => vec![pattern],
+        };
+        match rhs {
+            Pattern::Or(rhs) => patterns.extend(rhs),
+            rhs => patterns.push(rhs),
+        }
+        Pattern::Or(patterns)
+    }
+}
+
+/// Function which rewrites an attribute value.
+pub type Transform = Arc<dyn Fn(&str) -> Option<String> + Sync + Send>;
+
+/// Rule for an attribute value: either a pattern which keeps or drops a value,
+/// or a transformer which can also rewrite it.
+#[derive(Clone)]
+pub enum AttributeRule {
+    /// Keeps a value if it matches a pattern.
+    Pattern(Pattern),
+    /// Rewrites a value. `None` drops an attribute.
+    Transform(Transform),
+}
+
+impl fmt::Debug for AttributeRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttributeRule::Pattern(pattern) => f.debug_tuple("Pattern").field(pattern).finish(),
+            AttributeRule::Transform(_) => f.write_str("Transform(..)"),
+        }
+    }
+}
+
+impl AttributeRule {
+    /// Creates rule which rewrites a value.
+    ///
+    /// # Example
+    /// ```
+    /// use sanitize_html::rules::pattern::AttributeRule;
+    ///
+    /// let rule = AttributeRule::transform(|value| {
+    ///     let width: u32 = value.trim().parse().ok()?;
+    ///     Some(width.min(640).to_string())
+    /// });
+    /// assert_eq!(rule.apply(" 1000 ").as_deref(), Some("640"));
+    /// assert_eq!(rule.apply("100"), Some("100".into()));
+    /// assert_eq!(rule.apply("wide"), None);
+    /// ```
+    pub fn transform<F>(transform: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Sync + Send + 'static,
+    {
+        AttributeRule::Transform(Arc::new(transform))
+    }
+
+    /// Applies rule to a value. Returns a value to keep or `None` if an attribute should be dropped.
+    pub fn apply<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        match self {
+            AttributeRule::Pattern(pattern) => {
+                pattern.matches(value).then_some(Cow::Borrowed(value))
+            }
+            AttributeRule::Transform(transform) => transform(value).map(Cow::Owned),
+        }
+    }
+}
+
+impl From<Pattern> for AttributeRule {
+    fn from(pattern: Pattern) -> Self {
+        AttributeRule::Pattern(pattern)
     }
 }
@@ -2,32 +2,38 @@
 //!
 //! These rules are inspired by a great Ruby gem [sanitize](https://github.com/rgrove/sanitize/).
 
+use super::css::CssRules;
+use super::link::LinkPolicy;
 use super::pattern::Pattern;
+use super::url::UrlPolicy;
 use super::{Element, Rules};
-use regex::Regex;
-use std::sync::{Arc, LazyLock};
-
-static HREF_SCHEME_REGEX: LazyLock<Arc<Regex>> =
-    LazyLock::new(|| Arc::new(Regex::new("^(ftp:|http:|https:|mailto:)").unwrap()));
-
-static SRC_SCHEME_REGEX: LazyLock<Arc<Regex>> =
-    LazyLock::new(|| Arc::new(Regex::new("^(http:|https:)").unwrap()));
-
-static SCHEME_LIKE_REGEX: LazyLock<Arc<Regex>> =
-    LazyLock::new(|| Arc::new(Regex::new("^[^/]+[[:space:]]*:").unwrap()));
+use std::sync::LazyLock;
 
 fn href() -> Pattern {
-    Pattern(Box::new(move |value| {
-        HREF_SCHEME_REGEX.is_match(value) || !SCHEME_LIKE_REGEX.is_match(value)
-    }))
+    Pattern::url(
+        UrlPolicy::new()
+            .scheme("ftp")
+            .scheme("http")
+            .scheme("https")
+            .scheme("mailto")
+            .allow_relative(true)
+            .allow_protocol_relative(true),
+    )
 }
 
 fn src() -> Pattern {
-    Pattern(Box::new(move |value| {
-        SRC_SCHEME_REGEX.is_match(value) || !SCHEME_LIKE_REGEX.is_match(value)
-    }))
+    Pattern::url(
+        UrlPolicy::new()
+            .scheme("http")
+            .scheme("https")
+            .allow_relative(true)
+            .allow_protocol_relative(true),
+    )
 }
 
+/// CSS rules for `style` elements. Allows common presentational properties and `@media`.
+pub static STYLESHEET: LazyLock<CssRules> = LazyLock::new(stylesheet);
+
 /// Basic rules. Allows a variety of markup including formatting elements, links, and lists.
 pub static BASIC: LazyLock<Rules> = LazyLock::new(basic);
 
@@ -41,8 +47,82 @@ pub static RELAXED: LazyLock<Rules> = LazyLock::new(relaxed);
 pub static RESTRICTED: LazyLock<Rules> = LazyLock::new(restricted);
 
 /// Rules for document from untrusted sources. Removes all tags but text emphasizing and links.
+///
+/// Links with an allowed `href` get `rel="nofollow ugc noopener noreferrer"` after harmless
+/// link types of an author, such as `me` or `license`, and links to other hosts get
+/// `target="_blank"`. Earlier versions replaced `rel` of an author with
+/// `noreferrer noopener` and added `target="_blank"` to relative links too.
 pub static UNTRUSTED: LazyLock<Rules> = LazyLock::new(untrusted);
 
+fn stylesheet() -> CssRules {
+    const PROPERTIES: &[&str] = &[
+        "background",
+        "background-color",
+        "background-image",
+        "background-position",
+        "background-repeat",
+        "background-size",
+        "border",
+        "border-bottom",
+        "border-collapse",
+        "border-color",
+        "border-left",
+        "border-radius",
+        "border-right",
+        "border-spacing",
+        "border-style",
+        "border-top",
+        "border-width",
+        "color",
+        "font",
+        "font-family",
+        "font-size",
+        "font-style",
+        "font-variant",
+        "font-weight",
+        "height",
+        "letter-spacing",
+        "line-height",
+        "list-style",
+        "list-style-position",
+        "list-style-type",
+        "margin",
+        "margin-bottom",
+        "margin-left",
+        "margin-right",
+        "margin-top",
+        "max-height",
+        "max-width",
+        "min-height",
+        "min-width",
+        "padding",
+        "padding-bottom",
+        "padding-left",
+        "padding-right",
+        "padding-top",
+        "text-align",
+        "text-decoration",
+        "text-indent",
+        "text-transform",
+        "vertical-align",
+        "white-space",
+        "width",
+        "word-break",
+        "word-spacing",
+        "word-wrap",
+    ];
+
+    PROPERTIES
+        .iter()
+        .fold(CssRules::new(), |rules, property| {
+            rules.property(property, Pattern::any())
+        })
+        .url_scheme("http")
+        .url_scheme("https")
+        .allow_relative_urls(true)
+        .at_rule("media")
+}
+
 fn basic() -> Rules {
     Rules::new()
         .element(Element::new("a").attribute("href", href()))
@@ -50,7 +130,6 @@ fn basic() -> Rules {
         .element(Element::new("b"))
         .element(Element::new("blockquote").attribute("cite", src()))
         .element(Element::new("br"))
-        .element(Element::new("br"))
         .element(Element::new("cite"))
         .element(Element::new("code"))
         .element(Element::new("dd"))
@@ -306,12 +385,7 @@ fn restricted() -> Rules {
 
 fn untrusted() -> Rules {
     Rules::new()
-        .element(
-            Element::new("a")
-                .attribute("href", href())
-                .mandatory_attribute("target", "_blank")
-                .mandatory_attribute("rel", "noreferrer noopener"),
-        )
+        .element(Element::new("a").attribute("href", href()))
         .element(Element::new("b"))
         .element(Element::new("em"))
         .element(Element::new("i"))
@@ -346,11 +420,36 @@ fn untrusted() -> Rules {
         .delete("noscript")
         .delete("script")
         .delete("style")
+        .link_policy(
+            [
+                "author",
+                "bookmark",
+                "external",
+                "help",
+                "license",
+                "me",
+                "next",
+                "prev",
+                "sponsored",
+                "tag",
+            ]
+            .into_iter()
+            .fold(LinkPolicy::user_content(), LinkPolicy::allow_rel)
+            .rel("noreferrer")
+            .target_blank(true),
+        )
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{basic, default, relaxed, restricted, untrusted};
+    use super::{basic, default, relaxed, restricted, stylesheet, untrusted};
+
+    #[test]
+    fn stylesheet_does_not_fail() {
+        let rules = stylesheet();
+        assert_eq!(rules.properties.len(), 54);
+        assert_eq!(rules.at_rules.len(), 1);
+    }
 
     #[test]
     fn basic_does_not_fail() {
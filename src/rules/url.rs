@@ -0,0 +1,326 @@
+//! Rules to check URLs the way browsers parse them.
+//!
+//! # Examples
+//! ```
+//! use sanitize_html::rules::pattern::Pattern;
+//! use sanitize_html::rules::url::UrlPolicy;
+//!
+//! let href = Pattern::url(
+//!     UrlPolicy::new()
+//!         .scheme("http")
+//!         .scheme("https")
+//!         .allow_relative(true),
+//! );
+//!
+//! assert!(href.matches("https://example.com/"));
+//! assert!(href.matches("/about"));
+//! assert!(!href.matches("JavaScript:alert(1)"));
+//! assert!(!href.matches("java\tscript:alert(1)"));
+//! assert!(!href.matches("\u{1}javascript:alert(1)"));
+//! ```
+
+pub use ::url::Url;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Base used to resolve protocol-relative URLs. Only its scheme matters.
+const PROTOCOL_RELATIVE_BASE: &str = "https://base.invalid/";
+
+/// Removes characters which URL parser ignores: leading and trailing C0 controls and spaces,
+/// ASCII tabs and newlines anywhere.
+fn strip_ignored(value: &str) -> String {
+    value
+        .trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+/// Checks if a value starts with a scheme followed by a colon.
+fn has_scheme(value: &str) -> bool {
+    let Some(colon) = value.find(':') else {
+        return false;
+    };
+    let mut chars = value[..colon].chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn is_protocol_relative(value: &str) -> bool {
+    let mut chars = value.chars();
+    matches!(chars.next(), Some('/' | '\\')) && matches!(chars.next(), Some('/' | '\\'))
+}
+
+/// Attributes which hold a single URL.
+pub(crate) const URL_ATTRIBUTES: &[&str] = &[
+    "action",
+    "background",
+    "cite",
+    "codebase",
+    "data",
+    "formaction",
+    "href",
+    "icon",
+    "longdesc",
+    "manifest",
+    "poster",
+    "profile",
+    "src",
+    "usemap",
+];
+
+/// Resolves a relative URL against a base. Returns `None` if the URL is already absolute
+/// or can not be resolved.
+pub(crate) fn resolve(base: &Url, value: &str) -> Option<String> {
+    if has_scheme(&strip_ignored(value)) {
+        return None;
+    }
+    base.join(value.trim()).ok().map(String::from)
+}
+
+/// Returns a host of an absolute or protocol-relative URL in lower case, without a trailing dot.
+pub(crate) fn host(value: &str) -> Option<String> {
+    let value = strip_ignored(value);
+    let url = if has_scheme(&value) {
+        Url::parse(&value).ok()?
+    } else if is_protocol_relative(&value) {
+        Url::parse(PROTOCOL_RELATIVE_BASE).ok()?.join(&value).ok()?
+    } else {
+        return None;
+    };
+    let host = url.host_str()?;
+    Some(host.strip_suffix('.').unwrap_or(host).to_owned())
+}
+
+/// structure to describe allowed URLs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct UrlPolicy {
+    /// Allowed schemes in lower case.
+    pub schemes: HashSet<String>,
+    /// Determines if relative URLs (e.g. `/about` or `image.png`) are allowed.
+    pub allow_relative: bool,
+    /// Determines if protocol-relative URLs (e.g. `//example.com/`) are allowed.
+    pub allow_protocol_relative: bool,
+    /// Allowed hosts. If both hosts and host suffixes are empty, any host is allowed.
+    pub hosts: HashSet<String>,
+    /// Allowed host suffixes, e.g. `example.com` allows `example.com` and `www.example.com`.
+    pub host_suffixes: Vec<String>,
+}
+
+impl UrlPolicy {
+    /// Creates a policy which allows nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an allowed scheme
+    pub fn scheme(mut self, scheme: &str) -> Self {
+        self.schemes.insert(scheme.to_ascii_lowercase());
+        self
+    }
+
+    /// Sets if relative URLs are allowed
+    pub fn allow_relative(mut self, allow_relative: bool) -> Self {
+        self.allow_relative = allow_relative;
+        self
+    }
+
+    /// Sets if protocol-relative URLs are allowed
+    pub fn allow_protocol_relative(mut self, allow_protocol_relative: bool) -> Self {
+        self.allow_protocol_relative = allow_protocol_relative;
+        self
+    }
+
+    /// Adds an allowed host
+    pub fn host(mut self, host: &str) -> Self {
+        self.hosts.insert(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Adds an allowed host suffix. Host matches if it is equal to the suffix or is its subdomain.
+    pub fn host_suffix(mut self, suffix: &str) -> Self {
+        self.host_suffixes
+            .push(suffix.trim_start_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    fn is_host_allowed(&self, url: &Url) -> bool {
+        if self.hosts.is_empty() && self.host_suffixes.is_empty() {
+            return true;
+        }
+        let Some(host) = url.host_str() else {
+            // there is nothing to check in URLs like `mailto:`
+            return true;
+        };
+        // `example.com.` is the same host as `example.com`
+        let host = host.strip_suffix('.').unwrap_or(host);
+        self.hosts.contains(host)
+            || self.host_suffixes.iter().any(|suffix| {
+                host == suffix
+                    || host
+                        .strip_suffix(suffix.as_str())
+                        .is_some_and(|subdomain| subdomain.ends_with('.'))
+            })
+    }
+
+    /// Checks if a URL is allowed.
+    pub fn allows(&self, value: &str) -> bool {
+        let value = strip_ignored(value);
+        if has_scheme(&value) {
+            match Url::parse(&value) {
+                Ok(url) => self.schemes.contains(url.scheme()) && self.is_host_allowed(&url),
+                Err(_) => false,
+            }
+        } else if is_protocol_relative(&value) {
+            self.allow_protocol_relative
+                && Url::parse(PROTOCOL_RELATIVE_BASE)
+                    .and_then(|base| base.join(&value))
+                    .is_ok_and(|url| self.is_host_allowed(&url))
+        } else {
+            // A colon in the first segment is ambiguous with a scheme, URL spec does not allow it.
+            let first_segment = value.split(['/', '?', '#']).next().unwrap_or_default();
+            self.allow_relative && !first_segment.contains(':')
+        }
+    }
+}
+
+/// Writes sorted items separated by commas.
+fn write_sorted<'a>(
+    f: &mut fmt::Formatter,
+    items: impl Iterator<Item = &'a String>,
+) -> fmt::Result {
+    let mut items: Vec<_> = items.collect();
+    items.sort();
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        f.write_str(item)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for UrlPolicy {
+    /// Describes a policy, e.g. `URL (schemes: http, https; relative)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("URL (schemes: ")?;
+        if self.schemes.is_empty() {
+            f.write_str("none")?;
+        }
+        write_sorted(f, self.schemes.iter())?;
+        if self.allow_relative {
+            f.write_str("; relative")?;
+        }
+        if self.allow_protocol_relative {
+            f.write_str("; protocol-relative")?;
+        }
+        if !self.hosts.is_empty() {
+            f.write_str("; hosts: ")?;
+            write_sorted(f, self.hosts.iter())?;
+        }
+        if !self.host_suffixes.is_empty() {
+            f.write_str("; host suffixes: ")?;
+            write_sorted(f, self.host_suffixes.iter())?;
+        }
+        f.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlPolicy;
+    use ::url::Url;
+
+    fn policy() -> UrlPolicy {
+        UrlPolicy::new()
+            .scheme("https")
+            .scheme("mailto")
+            .allow_relative(true)
+            .allow_protocol_relative(true)
+    }
+
+    #[test]
+    fn schemes() {
+        let policy = policy();
+        assert!(policy.allows("https://example.com/"));
+        assert!(policy.allows("HTTPS://example.com/"));
+        assert!(policy.allows("mailto:user@example.com"));
+        assert!(!policy.allows("http://example.com/"));
+        assert!(!policy.allows("javascript:alert(1)"));
+        assert!(!policy.allows("JaVaScRiPt:alert(1)"));
+        assert!(!policy.allows(" \u{0}\u{1f}javascript:alert(1)"));
+        assert!(!policy.allows("java\nscr\tipt:alert(1)"));
+        assert!(!policy.allows("javascript :alert(1)"));
+        assert!(!policy.allows(":javascript:alert(1)"));
+        assert!(!policy.allows("jav%61script:alert(1)"));
+    }
+
+    #[test]
+    fn relative() {
+        let policy = policy();
+        assert!(policy.allows("image.png"));
+        assert!(policy.allows("/a/b?c=d:e#f:g"));
+        assert!(policy.allows("//example.com/a"));
+        assert!(policy.allows("\\\\example.com/a"));
+
+        let absolute_only = UrlPolicy::new().scheme("https");
+        assert!(!absolute_only.allows("image.png"));
+        assert!(!absolute_only.allows("//example.com/a"));
+    }
+
+    #[test]
+    fn resolve() {
+        let base = Url::parse("https://example.com/posts/1/").unwrap();
+        assert_eq!(
+            super::resolve(&base, "../2/?a=b#c").as_deref(),
+            Some("https://example.com/posts/2/?a=b#c")
+        );
+        assert_eq!(
+            super::resolve(&base, " //cdn.example.com/a.png ").as_deref(),
+            Some("https://cdn.example.com/a.png")
+        );
+        assert_eq!(
+            super::resolve(&base, "#top").as_deref(),
+            Some("https://example.com/posts/1/#top")
+        );
+        assert_eq!(super::resolve(&base, "mailto:user@example.com"), None);
+        assert_eq!(super::resolve(&base, "HTTP://other.com/"), None);
+    }
+
+    #[test]
+    fn hosts() {
+        let policy = policy().host("example.com").host_suffix("youtube.com");
+        assert!(policy.allows("https://example.com/"));
+        assert!(policy.allows("https://EXAMPLE.com./"));
+        assert!(!policy.allows("https://www.example.com/"));
+        assert!(policy.allows("https://youtube.com/"));
+        assert!(policy.allows("https://www.youtube.com/"));
+        assert!(!policy.allows("https://notyoutube.com/"));
+        assert!(!policy.allows("https://youtube.com.evil.com/"));
+        assert!(!policy.allows("https://youtube.com@evil.com/"));
+        assert!(policy.allows("//www.youtube.com/embed/x"));
+        assert!(!policy.allows("//evil.com/"));
+        assert!(policy.allows("mailto:user@example.com"));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            policy().to_string(),
+            "URL (schemes: https, mailto; relative; protocol-relative)"
+        );
+        let policy = UrlPolicy::new()
+            .host("b.com")
+            .host("a.com")
+            .host_suffix("c.com");
+        assert_eq!(
+            policy.to_string(),
+            "URL (schemes: none; hosts: a.com, b.com; host suffixes: c.com)"
+        );
+    }
+}
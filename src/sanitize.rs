@@ -1,9 +1,22 @@
-use crate::rcdom::{Node, NodeData, RcDom};
-use crate::rules::{Element, Rules};
+use crate::dom::Document;
+use crate::errors::{Limit, SanitizeError};
+use crate::parse;
+use crate::rcdom::{Node, NodeData, append};
+use crate::report::{ChangeKind, Rejection, Report};
+use crate::rules::action::{Content, ElementAction, ElementContext};
+use crate::rules::css::{Rejected, serialize_declarations};
+use crate::rules::limits::OnLimit;
+use crate::rules::pattern::AttributeRule;
+use crate::rules::predefined::STYLESHEET;
+use crate::rules::url::{URL_ATTRIBUTES, resolve};
+use crate::rules::{Element, MandatoryAttribute, RenameAttributes, Rules};
 use html5ever::{Attribute, LocalName, interface::QualName, ns, tendril::StrTendril};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::LazyLock;
 use std::{cell::RefCell, rc::Rc};
 
-fn simple_qual_name(name: &str) -> QualName {
+pub(crate) fn simple_qual_name(name: &str) -> QualName {
     QualName::new(None, ns!(), LocalName::from(name))
 }
 
@@ -22,7 +35,9 @@ fn simple_element(name: QualName, attrs: Vec<Attribute>, children: Vec<Rc<Node>>
         template_contents: Default::default(),
         mathml_annotation_xml_integration_point: Default::default(),
     });
-    element.children.borrow_mut().extend(children);
+    for child in children {
+        append(&element, child);
+    }
     element
 }
 
@@ -32,124 +47,911 @@ fn create_space_text() -> Rc<Node> {
     })
 }
 
-enum ElementAction<'t> {
+/// Element rule used to keep an element which is not allowed by rules but kept by a transformer.
+static NO_RULE: LazyLock<Element> = LazyLock::new(|| Element::new(""));
+
+pub(crate) enum Action<'t> {
     Keep(&'t Element),
     Delete,
     Space,
     Elide,
-    Rename(&'t str),
-}
-
-fn element_action<'t>(element_name: &QualName, rules: &'t Rules) -> ElementAction<'t> {
-    let name = qual_name_to_string(element_name);
-    if name == "html" || name == "body" {
-        ElementAction::Elide
-    } else if let Some(element_sanitizer) = rules.allowed_elements.get(&name) {
-        ElementAction::Keep(element_sanitizer)
-    } else if rules.delete_elements.contains(&name) {
-        ElementAction::Delete
-    } else if rules.space_elements.contains(&name) {
-        ElementAction::Space
-    } else if let Some(rename_to) = rules.rename_elements.get(&name) {
-        ElementAction::Rename(rename_to)
-    } else {
-        ElementAction::Elide
+    Rename(Cow<'t, str>, Cow<'t, RenameAttributes>),
+    Replace(Vec<Content>),
+}
+
+pub(crate) fn content_to_node(content: Content) -> Rc<Node> {
+    match content {
+        Content::Text(text) => Node::new(NodeData::Text {
+            contents: RefCell::new(text.into()),
+        }),
+        Content::Element {
+            name,
+            attributes,
+            children,
+        } => simple_element(
+            simple_qual_name(&name),
+            attributes
+                .into_iter()
+                .map(|(name, value)| Attribute {
+                    name: simple_qual_name(&name),
+                    value: value.into(),
+                })
+                .collect(),
+            children.into_iter().map(content_to_node).collect(),
+        ),
+    }
+}
+
+pub(crate) fn is_structural(name: &str) -> bool {
+    matches!(name, "html" | "head" | "body")
+}
+
+/// Checks if content of an HTML element is parsed as text, so it cannot contain elements.
+pub(crate) fn is_raw_text(name: &str) -> bool {
+    matches!(
+        name,
+        "iframe"
+            | "noembed"
+            | "noframes"
+            | "noscript"
+            | "plaintext"
+            | "script"
+            | "style"
+            | "textarea"
+            | "title"
+            | "xmp"
+    )
+}
+
+/// Checks if text of an HTML element is serialized without escaping.
+fn is_unescaped(name: &str) -> bool {
+    matches!(
+        name,
+        "iframe" | "noembed" | "noframes" | "plaintext" | "script" | "style" | "xmp"
+    )
+}
+
+/// Escapes end tags of `name` in text, which is not escaped when written into the element,
+/// e.g. `</script` becomes `<\/script`. Returns `None` if there are none.
+fn escape_end_tags(text: &str, name: &str) -> Option<String> {
+    let end_tag = format!("</{}", name);
+    let lowercase = text.to_ascii_lowercase();
+    if !lowercase.contains(&end_tag) {
+        return None;
+    }
+    let mut escaped = String::with_capacity(text.len() + 1);
+    let mut last = 0;
+    for (index, _) in lowercase.match_indices(&end_tag) {
+        escaped.push_str(&text[last..=index]);
+        escaped.push('\\');
+        last = index + 1;
     }
+    escaped.push_str(&text[last..]);
+    Some(escaped)
 }
 
-fn clean_nodes(nodes: &[Rc<Node>], rules: &Rules) -> Vec<Rc<Node>> {
-    nodes
+fn has_attribute(attrs: &[Attribute], name: &str, value: &str) -> bool {
+    attrs
         .iter()
-        .flat_map(|node| clean_node(node, rules))
-        .collect()
+        .any(|attr| qual_name_to_string(&attr.name) == name && &*attr.value == value)
 }
 
-fn clean_node(node: &Rc<Node>, rules: &Rules) -> Vec<Rc<Node>> {
-    match node.data {
-        NodeData::Document => vec![],
-        NodeData::Doctype { .. } => vec![],
-        NodeData::ProcessingInstruction { .. } => vec![],
+/// Returns a value of an attribute without a namespace.
+fn attribute_value<'a>(attrs: &'a [Attribute], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|attr| attr.name.ns == ns!() && &*attr.name.local == name)
+        .map(|attr| &*attr.value)
+}
+
+fn simple_attribute(name: &str, value: &str) -> Attribute {
+    Attribute {
+        name: QualName {
+            prefix: None,
+            ns: ns!(),
+            local: LocalName::from(name),
+        },
+        value: StrTendril::from(value),
+    }
+}
+
+/// Numbers element siblings the way XPath does: `div[1]`, `p[1]`, `div[2]`.
+#[derive(Default)]
+pub(crate) struct SiblingCounter(HashMap<String, usize>);
+
+impl SiblingCounter {
+    fn segment(&mut self, node: &Node) -> String {
+        let name = match node.data {
+            NodeData::Element { ref name, .. } => qual_name_to_string(name),
+            NodeData::Comment { .. } => "comment()".to_owned(),
+            NodeData::Text { .. } => "text()".to_owned(),
+            _ => "node()".to_owned(),
+        };
+        self.named_segment(name)
+    }
+
+    /// Builds a segment for a node test, e.g. an element name or `comment()`.
+    pub(crate) fn named_segment(&mut self, name: String) -> String {
+        let index = self.0.entry(name.clone()).or_default();
+        *index += 1;
+        format!("{}[{}]", name, index)
+    }
+}
+
+/// What becomes of an element when all of its children are cleaned.
+enum Wrap {
+    /// Children are a new document.
+    Document,
+    /// Children are put into a new element.
+    Element(QualName, Vec<Attribute>),
+    /// Children replace the element.
+    Elide,
+    /// Children replace the element and are surrounded by spaces.
+    /// `start` is a length of the result after a leading space.
+    Space { start: usize },
+}
+
+/// Result of cleaning a single node.
+enum Cleaned {
+    /// Nodes which replace the node in the result.
+    Nodes(Vec<Rc<Node>>),
+    /// Children of the node have to be cleaned, then wrapped.
+    Children(Wrap),
+}
+
+/// Node, whose children are being cleaned.
+struct Frame {
+    node: Rc<Node>,
+    /// Index of a next child to clean.
+    next: usize,
+    counter: SiblingCounter,
+    wrap: Wrap,
+    /// Node is counted in the nesting depth.
+    counted: bool,
+    /// Index of a frame, which collects cleaned children. Children of elided elements go
+    /// directly to the closest kept ancestor, so they are not moved at every level.
+    output: usize,
+    /// Cleaned children, if the frame collects them.
+    result: Vec<Rc<Node>>,
+}
+
+impl Frame {
+    fn new(node: Rc<Node>, wrap: Wrap, counted: bool, output: usize) -> Self {
+        Frame {
+            node,
+            next: 0,
+            counter: SiblingCounter::default(),
+            wrap,
+            counted,
+            output,
+            result: Vec::new(),
+        }
+    }
+}
+
+pub(crate) struct Sanitizer<'r> {
+    pub(crate) rules: &'r Rules,
+    pub(crate) report: Option<Report>,
+    pub(crate) path: Vec<String>,
+    /// Names of ancestors of a current node, tracked only for a transformer.
+    pub(crate) ancestors: Vec<String>,
+    /// Nesting depth of a current element, not counting `html` and `body`.
+    pub(crate) depth: usize,
+    /// Number of nodes seen so far.
+    pub(crate) nodes: usize,
+    /// Rest of an input is dropped, because the node limit was reached.
+    pub(crate) truncated: bool,
+    /// Limit error, which stops sanitization.
+    pub(crate) error: Option<SanitizeError>,
+}
+
+impl<'r> Sanitizer<'r> {
+    pub(crate) fn new(rules: &'r Rules, with_report: bool) -> Self {
+        Self {
+            rules,
+            report: with_report.then(Report::default),
+            path: Vec::new(),
+            ancestors: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            truncated: false,
+            error: None,
+        }
+    }
+
+    pub(crate) fn element_action(&self, name: &str, attrs: &[Attribute]) -> Action<'r> {
+        let rules = self.rules;
+        if name == "html" || name == "body" {
+            return Action::Elide;
+        }
+        if let Some(ref transformer) = rules.transformer {
+            let attributes: Vec<(String, String)> = attrs
+                .iter()
+                .map(|attr| (qual_name_to_string(&attr.name), attr.value.to_string()))
+                .collect();
+            let context = ElementContext {
+                name,
+                attributes: &attributes,
+                ancestors: &self.ancestors,
+            };
+            if let Some(action) = transformer(&context) {
+                return match action {
+                    ElementAction::Keep => {
+                        Action::Keep(rules.allowed_elements.get(name).unwrap_or(&NO_RULE))
+                    }
+                    ElementAction::Delete => Action::Delete,
+                    ElementAction::Space => Action::Space,
+                    ElementAction::Elide => Action::Elide,
+                    ElementAction::Rename(to) => {
+                        Action::Rename(Cow::Owned(to), Cow::Owned(RenameAttributes::Drop))
+                    }
+                    ElementAction::RenameWith(to, attributes) => {
+                        Action::Rename(Cow::Owned(to), Cow::Owned(attributes))
+                    }
+                    ElementAction::Replace(nodes) => Action::Replace(nodes),
+                };
+            }
+        }
+
+        if let Some(element_sanitizer) = rules.allowed_elements.get(name) {
+            Action::Keep(element_sanitizer)
+        } else if rules.delete_elements.contains(name) {
+            Action::Delete
+        } else if rules.space_elements.contains(name) {
+            Action::Space
+        } else if let Some(rename) = rules.rename_elements.get(name) {
+            Action::Rename(Cow::Borrowed(&rename.to), Cow::Borrowed(&rename.attributes))
+        } else {
+            Action::Elide
+        }
+    }
 
-        NodeData::Text { .. } => vec![node.clone()],
+    pub(crate) fn record(&mut self, kind: impl FnOnce() -> ChangeKind) {
+        if let Some(report) = self.report.as_mut() {
+            report.push(format!("/{}", self.path.join("/")), kind());
+        }
+    }
 
-        NodeData::Comment { .. } => {
-            if rules.allow_comments {
-                vec![node.clone()]
-            } else {
-                vec![]
+    /// Checks `value` against a limit. If it is exceeded, either stops sanitization with an error
+    /// or records that content is removed, depending on the rules.
+    pub(crate) fn exceeds(&mut self, limit: Limit, max: Option<usize>, value: usize) -> bool {
+        let Some(max) = max.filter(|&max| value > max) else {
+            return false;
+        };
+        match self.rules.limits.on_exceed {
+            OnLimit::Fail => {
+                self.error
+                    .get_or_insert(SanitizeError::LimitExceeded { limit, max });
+            }
+            OnLimit::Truncate | OnLimit::Flatten => {
+                self.record(|| ChangeKind::LimitExceeded { limit, max });
             }
         }
+        true
+    }
 
-        NodeData::Element {
-            ref name,
-            ref attrs,
-            ..
-        } => {
-            match element_action(name, rules) {
-                ElementAction::Keep(element_sanitizer) => {
-                    let mut new_attrs: Vec<Attribute> = Vec::new();
-
-                    /* allowlisted attributes */
-                    for attr in attrs.borrow().iter() {
-                        if element_sanitizer.is_valid(&qual_name_to_string(&attr.name), &attr.value)
-                        {
-                            new_attrs.push(attr.clone());
-                        }
+    /// Nothing more is added to the result.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.truncated || self.error.is_some()
+    }
+
+    /// Counts a node, except `html`, `head` and `body`, which a parser may add.
+    /// Returns `false` if the node is over the node limit.
+    pub(crate) fn count_node(&mut self) -> bool {
+        if self.is_stopped() {
+            return false;
+        }
+        self.nodes += 1;
+        if self.exceeds(Limit::Nodes, self.rules.limits.max_nodes, self.nodes) {
+            self.truncated = true;
+            return false;
+        }
+        true
+    }
+
+    /// Drops attributes over the attribute limit.
+    pub(crate) fn limit_attributes<'a>(&mut self, attrs: &'a [Attribute]) -> &'a [Attribute] {
+        let max = self.rules.limits.max_attributes;
+        match self.exceeds(Limit::Attributes, max, attrs.len()) {
+            true => &attrs[..max.unwrap_or_default()],
+            false => attrs,
+        }
+    }
+
+    /// Cleans children of a document. Nodes are walked with an explicit stack instead of
+    /// recursion, so deeply nested input does not overflow the call stack.
+    fn clean_document(&mut self, document: &Rc<Node>) -> Vec<Rc<Node>> {
+        let mut stack = vec![Frame::new(document.clone(), Wrap::Document, false, 0)];
+        loop {
+            let frame = stack.last_mut().expect("document frame");
+            let child = match self.is_stopped() {
+                true => None,
+                false => frame.node.children.borrow().get(frame.next).cloned(),
+            };
+            let Some(child) = child else {
+                let frame = stack.pop().expect("document frame");
+                if let Some(nodes) = self.finish(frame, &mut stack) {
+                    return nodes;
+                }
+                continue;
+            };
+            frame.next += 1;
+            let output = frame.output;
+
+            let structural = match child.data {
+                NodeData::Element { ref name, .. } => is_structural(&qual_name_to_string(name)),
+                _ => false,
+            };
+            if !structural && !self.count_node() {
+                continue;
+            }
+            if self.report.is_some() {
+                self.path.push(frame.counter.segment(&child));
+            }
+            match self.clean_node(&child) {
+                Cleaned::Nodes(nodes) => {
+                    stack[output].result.extend(nodes);
+                    if self.report.is_some() {
+                        self.path.pop();
                     }
+                }
+                Cleaned::Children(mut wrap) => {
+                    if !structural {
+                        self.depth += 1;
+                    }
+                    let output = match wrap {
+                        Wrap::Element(..) => stack.len(),
+                        Wrap::Space { ref mut start } => {
+                            let result = &mut stack[output].result;
+                            result.push(create_space_text());
+                            *start = result.len();
+                            output
+                        }
+                        Wrap::Document | Wrap::Elide => output,
+                    };
+                    stack.push(Frame::new(child, wrap, !structural, output));
+                }
+            }
+        }
+    }
 
-                    /* mandatory attributes */
-                    let mut mandatory_attributes: Vec<(&String, &String)> =
-                        element_sanitizer.mandatory_attributes.iter().collect();
-                    mandatory_attributes.sort();
-                    for &(attr_name, attr_value) in mandatory_attributes.iter() {
+    /// Wraps children of an element when all of them are cleaned.
+    /// Returns the result for a document frame.
+    fn finish(&mut self, frame: Frame, stack: &mut [Frame]) -> Option<Vec<Rc<Node>>> {
+        let Frame {
+            wrap,
+            counted,
+            output,
+            result,
+            ..
+        } = frame;
+        if counted {
+            self.depth -= 1;
+        }
+        if let Wrap::Document = wrap {
+            return Some(result);
+        }
+        if self.rules.transformer.is_some() {
+            self.ancestors.pop();
+        }
+        if self.report.is_some() {
+            self.path.pop();
+        }
+        match wrap {
+            Wrap::Document | Wrap::Elide => {}
+            Wrap::Element(name, attrs) => {
+                let output = stack.last().expect("document frame").output;
+                stack[output]
+                    .result
+                    .push(simple_element(name, attrs, result));
+            }
+            Wrap::Space { start } => {
+                let result = &mut stack[output].result;
+                if result.len() != start {
+                    result.push(create_space_text());
+                }
+            }
+        }
+        None
+    }
+
+    /// Cleans content of an element which is text in the output. Child elements are dropped,
+    /// because their tags would be written into the text, a stylesheet is sanitized,
+    /// and end tags in unescaped text are escaped. Text of an element without a namespace,
+    /// e.g. a renamed one, is always escaped.
+    fn clean_text_content(&mut self, node: &Rc<Node>, output: &QualName) -> Vec<Rc<Node>> {
+        let output_name = &*output.local;
+        let mut text = String::new();
+        let mut counter = SiblingCounter::default();
+        for child in node.children.borrow().iter() {
+            match child.data {
+                NodeData::Text { ref contents } => text.push_str(&contents.borrow()),
+                NodeData::Element { ref name, .. } if self.report.is_some() => {
+                    self.path.push(counter.segment(child));
+                    self.record(|| ChangeKind::ElementDropped {
+                        name: qual_name_to_string(name),
+                    });
+                    self.path.pop();
+                }
+                _ => {}
+            }
+        }
+
+        if output_name == "style" {
+            text = self.clean_stylesheet(&text, output_name);
+        }
+        // a parser never puts an end tag into the text, but a caller of the DOM API can
+        if output.ns == ns!(html)
+            && is_unescaped(output_name)
+            && let Some(escaped) = escape_end_tags(&text, output_name)
+        {
+            self.record(|| ChangeKind::RawTextEscaped {
+                element: output_name.to_owned(),
+                from: text.clone(),
+                to: escaped.clone(),
+            });
+            text = escaped;
+        }
+        if text.is_empty() {
+            vec![]
+        } else {
+            vec![Node::new(NodeData::Text {
+                contents: RefCell::new(text.into()),
+            })]
+        }
+    }
+
+    /// Sanitizes content of a `style` element, which is not escaped.
+    pub(crate) fn clean_stylesheet(&mut self, stylesheet: &str, output_name: &str) -> String {
+        let css_rules = self.rules.stylesheet.as_ref().unwrap_or(&STYLESHEET);
+        let mut rejected = Vec::new();
+        let sanitized = css_rules.filter_stylesheet(stylesheet, &mut rejected);
+        if rejected.is_empty() && sanitized != stylesheet {
+            self.record(|| ChangeKind::StylesheetChanged {
+                element: output_name.to_owned(),
+                from: stylesheet.to_owned(),
+                to: sanitized.clone(),
+            });
+        }
+        for item in rejected {
+            self.record(|| match item {
+                Rejected::Declaration(declaration) => ChangeKind::DeclarationRejected {
+                    element: output_name.to_owned(),
+                    property: declaration.name,
+                    value: declaration.value,
+                },
+                Rejected::Rule(rule) => ChangeKind::CssRuleRejected {
+                    element: output_name.to_owned(),
+                    rule,
+                },
+            });
+        }
+        sanitized
+    }
+
+    /// Filters attributes of a kept element and adds mandatory ones.
+    pub(crate) fn clean_attributes(
+        &mut self,
+        element_sanitizer: &Element,
+        element_name: &str,
+        attrs: &[Attribute],
+    ) -> Vec<Attribute> {
+        let rules = self.rules;
+        let mut new_attrs: Vec<Attribute> = Vec::new();
+
+        // a link policy applies only to links, which keep their `href`
+        let href = attribute_value(attrs, "href")
+            .filter(|href| rules.is_valid_attribute(element_sanitizer, "href", href));
+        let link_policy = rules
+            .link_policy
+            .as_ref()
+            .filter(|policy| href.is_some() && policy.applies_to(element_name));
+        let target_blank = link_policy.is_some_and(|policy| {
+            policy.target_blank && href.is_some_and(|href| policy.is_external(href))
+        });
+
+        /* allowlisted attributes */
+        for attr in attrs {
+            let attr_name = qual_name_to_string(&attr.name);
+            if (link_policy.is_some() && attr_name == "rel")
+                || (target_blank && attr_name == "target")
+            {
+                continue;
+            }
+            if attr_name == "style"
+                && let Some(ref css_rules) = element_sanitizer.style
+            {
+                let (allowed, rejected) = css_rules.filter_declarations(&attr.value);
+                for declaration in rejected {
+                    self.record(|| ChangeKind::DeclarationRejected {
+                        element: element_name.to_owned(),
+                        property: declaration.name,
+                        value: declaration.value,
+                    });
+                }
+                if !allowed.is_empty() {
+                    new_attrs.push(Attribute {
+                        name: attr.name.clone(),
+                        value: StrTendril::from(serialize_declarations(&allowed)),
+                    });
+                }
+                continue;
+            }
+            if attr_name == "class"
+                && let Some(policy) = rules.class_policy_for(element_sanitizer)
+            {
+                match policy.filter(&attr.value) {
+                    Some(value) => {
+                        if value != *attr.value {
+                            self.record(|| ChangeKind::AttributeChanged {
+                                element: element_name.to_owned(),
+                                name: attr_name.clone(),
+                                from: attr.value.to_string(),
+                                to: value.clone(),
+                            });
+                        }
                         new_attrs.push(Attribute {
-                            name: QualName {
-                                prefix: None,
-                                ns: ns!(),
-                                local: LocalName::from(attr_name.as_str()),
+                            name: attr.name.clone(),
+                            value: StrTendril::from(value),
+                        });
+                    }
+                    None => self.record(|| ChangeKind::AttributeRejected {
+                        element: element_name.to_owned(),
+                        name: attr_name,
+                        value: attr.value.to_string(),
+                        reason: Rejection::NoAllowedClasses,
+                    }),
+                }
+                continue;
+            }
+            let value = match rules.attribute_rule(element_sanitizer, &attr_name) {
+                None => Err(Rejection::NotAllowed),
+                Some(rule) => {
+                    rule.rule
+                        .apply(&attr.value)
+                        .ok_or_else(|| Rejection::PatternMismatch {
+                            scope: rule.scope,
+                            rule: rule.key.to_owned(),
+                            pattern: match rule.rule {
+                                AttributeRule::Pattern(pattern) => Some(pattern.to_string()),
+                                AttributeRule::Transform(_) => None,
                             },
-                            value: StrTendril::from(attr_value.as_str()),
+                        })
+                }
+            };
+            match value {
+                Ok(value) => {
+                    let value = self
+                        .rewrite_url(&attr_name, &value)
+                        .map(Cow::Owned)
+                        .unwrap_or(value);
+                    if *value != *attr.value {
+                        self.record(|| ChangeKind::AttributeChanged {
+                            element: element_name.to_owned(),
+                            name: attr_name.clone(),
+                            from: attr.value.to_string(),
+                            to: value.to_string(),
                         });
                     }
+                    new_attrs.push(Attribute {
+                        name: attr.name.clone(),
+                        value: StrTendril::from(value.as_ref()),
+                    });
+                }
+                Err(reason) => self.record(|| ChangeKind::AttributeRejected {
+                    element: element_name.to_owned(),
+                    name: attr_name,
+                    value: attr.value.to_string(),
+                    reason,
+                }),
+            }
+        }
 
-                    let children = clean_nodes(&node.children.borrow(), rules);
-                    let element = simple_element(name.clone(), new_attrs, children);
+        /* link policy */
+        if let Some(policy) = link_policy {
+            let source = attribute_value(attrs, "rel");
+            let rel = policy.filter_rel(source.unwrap_or_default());
+            match (source, &rel) {
+                (Some(from), Some(to)) if from != to => {
+                    self.record(|| ChangeKind::AttributeChanged {
+                        element: element_name.to_owned(),
+                        name: "rel".to_owned(),
+                        from: from.to_owned(),
+                        to: to.clone(),
+                    })
+                }
+                (Some(from), None) => self.record(|| ChangeKind::AttributeRejected {
+                    element: element_name.to_owned(),
+                    name: "rel".to_owned(),
+                    value: from.to_owned(),
+                    reason: Rejection::NoAllowedLinkTypes,
+                }),
+                (None, Some(to)) => self.record(|| ChangeKind::AttributeAdded {
+                    element: element_name.to_owned(),
+                    name: "rel".to_owned(),
+                    value: to.clone(),
+                }),
+                _ => {}
+            }
+            if let Some(rel) = rel {
+                new_attrs.push(simple_attribute("rel", &rel));
+            }
+        }
+        if target_blank {
+            match attribute_value(attrs, "target") {
+                Some("_blank") => {}
+                Some(from) => self.record(|| ChangeKind::AttributeChanged {
+                    element: element_name.to_owned(),
+                    name: "target".to_owned(),
+                    from: from.to_owned(),
+                    to: "_blank".to_owned(),
+                }),
+                None => self.record(|| ChangeKind::AttributeAdded {
+                    element: element_name.to_owned(),
+                    name: "target".to_owned(),
+                    value: "_blank".to_owned(),
+                }),
+            }
+            new_attrs.push(simple_attribute("target", "_blank"));
+        }
 
-                    vec![element]
+        /* mandatory attributes */
+        let mut mandatory_attributes: Vec<(&String, &MandatoryAttribute)> = element_sanitizer
+            .mandatory_attributes
+            .iter()
+            .filter(|(attr_name, _)| match attr_name.as_str() {
+                "rel" => link_policy.is_none(),
+                "target" => !target_blank,
+                _ => true,
+            })
+            .collect();
+        mandatory_attributes.sort_by_key(|&(attr_name, _)| attr_name);
+        for (attr_name, mandatory) in mandatory_attributes {
+            let kept = new_attrs
+                .iter()
+                .position(|attr| attr.name.ns == ns!() && *attr.name.local == **attr_name);
+            let kept_value = kept.map(|i| new_attrs[i].value.to_string());
+            let value = mandatory.apply(kept_value.as_deref());
+            match kept_value {
+                Some(from) if from != value => self.record(|| ChangeKind::AttributeChanged {
+                    element: element_name.to_owned(),
+                    name: attr_name.clone(),
+                    from,
+                    to: value.clone(),
+                }),
+                None if !has_attribute(attrs, attr_name, &value) => {
+                    self.record(|| ChangeKind::AttributeAdded {
+                        element: element_name.to_owned(),
+                        name: attr_name.clone(),
+                        value: value.clone(),
+                    })
                 }
+                _ => {}
+            }
+            match kept {
+                // a value from a document is replaced in place, so an attribute is not duplicated
+                Some(i) => new_attrs[i].value = StrTendril::from(value),
+                None => new_attrs.push(simple_attribute(attr_name, &value)),
+            }
+        }
 
-                ElementAction::Delete => vec![],
+        new_attrs
+    }
 
-                ElementAction::Elide => clean_nodes(&node.children.borrow(), rules),
+    /// Builds attributes of a renamed element from attributes of an original one.
+    /// They are checked against rules of the new element, which adds its mandatory attributes.
+    pub(crate) fn rename_attributes(
+        &mut self,
+        rename_to: &str,
+        attributes: &RenameAttributes,
+        attrs: &[Attribute],
+    ) -> Vec<Attribute> {
+        let mapped: Vec<Attribute>;
+        let attrs = match attributes {
+            RenameAttributes::Drop => {
+                self.reject_attributes(rename_to, attrs);
+                &[]
+            }
+            RenameAttributes::Keep => attrs,
+            RenameAttributes::Map(mapper) => {
+                let source: Vec<(String, String)> = attrs
+                    .iter()
+                    .map(|attr| (qual_name_to_string(&attr.name), attr.value.to_string()))
+                    .collect();
+                mapped = mapper(&source)
+                    .iter()
+                    .map(|(name, value)| simple_attribute(name, value))
+                    .collect();
+                &mapped
+            }
+        };
+        match self.rules.allowed_elements.get(rename_to) {
+            Some(element_sanitizer) => self.clean_attributes(element_sanitizer, rename_to, attrs),
+            None => {
+                self.reject_attributes(rename_to, attrs);
+                Vec::new()
+            }
+        }
+    }
 
-                ElementAction::Space => {
-                    let mut nodes = clean_nodes(&node.children.borrow(), rules);
-                    if nodes.is_empty() {
-                        nodes.push(create_space_text());
-                    } else {
-                        nodes.insert(0, create_space_text());
-                        nodes.push(create_space_text());
-                    }
-                    nodes
+    /// Records that attributes are removed from an element, because none are allowed.
+    fn reject_attributes(&mut self, element_name: &str, attrs: &[Attribute]) {
+        for attr in attrs {
+            self.record(|| ChangeKind::AttributeRejected {
+                element: element_name.to_owned(),
+                name: qual_name_to_string(&attr.name),
+                value: attr.value.to_string(),
+                reason: Rejection::NotAllowed,
+            });
+        }
+    }
+
+    /// Resolves a relative URL of an attribute if rules have a base URL.
+    fn rewrite_url(&self, attr_name: &str, value: &str) -> Option<String> {
+        match self.rules.base_url {
+            Some(ref base) if URL_ATTRIBUTES.contains(&attr_name) => resolve(base, value),
+            _ => None,
+        }
+    }
+
+    fn clean_node(&mut self, node: &Rc<Node>) -> Cleaned {
+        let rules = self.rules;
+        let nodes = match node.data {
+            NodeData::Document => vec![],
+            NodeData::Doctype { .. } => vec![],
+            NodeData::ProcessingInstruction { .. } => vec![],
+
+            NodeData::Text { ref contents } => vec![Node::new(NodeData::Text {
+                contents: RefCell::new(contents.borrow().clone()),
+            })],
+
+            NodeData::Comment { ref contents } => {
+                if rules.allow_comments {
+                    vec![Node::new(NodeData::Comment {
+                        contents: contents.clone(),
+                    })]
+                } else {
+                    self.record(|| ChangeKind::CommentStripped {
+                        text: contents.to_string(),
+                    });
+                    vec![]
+                }
+            }
+
+            NodeData::Element {
+                ref name,
+                ref attrs,
+                ..
+            } => {
+                let element_name = qual_name_to_string(name);
+                let counted = !is_structural(&element_name);
+                let max_depth = rules.limits.max_depth;
+                if counted && self.exceeds(Limit::Depth, max_depth, self.depth + 1) {
+                    return Cleaned::Nodes(match rules.limits.on_exceed {
+                        OnLimit::Flatten => text_content(node),
+                        _ => vec![],
+                    });
+                }
+
+                let attrs = attrs.borrow();
+                let attrs = self.limit_attributes(&attrs);
+                let action = self.element_action(&element_name, attrs);
+                let cleaned = self.clean_element(node, name, attrs, element_name.clone(), action);
+                if let Cleaned::Children(_) = cleaned
+                    && rules.transformer.is_some()
+                {
+                    self.ancestors.push(element_name);
+                }
+                return cleaned;
+            }
+        };
+        Cleaned::Nodes(nodes)
+    }
+
+    /// Applies an action to an element. Children of the element are cleaned later,
+    /// unless they are dropped or the element is a stylesheet.
+    fn clean_element(
+        &mut self,
+        node: &Rc<Node>,
+        name: &QualName,
+        attrs: &[Attribute],
+        element_name: String,
+        action: Action<'r>,
+    ) -> Cleaned {
+        match action {
+            Action::Keep(element_sanitizer) => {
+                let new_attrs = self.clean_attributes(element_sanitizer, &element_name, attrs);
+                if is_raw_text(&element_name) {
+                    let children = self.clean_text_content(node, name);
+                    return Cleaned::Nodes(vec![simple_element(name.clone(), new_attrs, children)]);
                 }
+                Cleaned::Children(Wrap::Element(name.clone(), new_attrs))
+            }
 
-                ElementAction::Rename(rename_to) => {
-                    let children = clean_nodes(&node.children.borrow(), rules);
-                    vec![simple_element(
-                        simple_qual_name(rename_to),
-                        Vec::new(),
-                        children,
-                    )]
+            Action::Delete => {
+                self.record(|| ChangeKind::ElementDropped { name: element_name });
+                Cleaned::Nodes(vec![])
+            }
+
+            Action::Elide => {
+                if !is_structural(&element_name) {
+                    self.record(|| ChangeKind::ElementElided { name: element_name });
                 }
+                Cleaned::Children(Wrap::Elide)
+            }
+
+            Action::Space => {
+                self.record(|| ChangeKind::ElementSpaced { name: element_name });
+                Cleaned::Children(Wrap::Space { start: 0 })
+            }
+
+            Action::Rename(rename_to, attributes) => {
+                self.record(|| ChangeKind::ElementRenamed {
+                    from: element_name,
+                    to: rename_to.to_string(),
+                });
+                let new_attrs = self.rename_attributes(&rename_to, &attributes, attrs);
+                let name = simple_qual_name(&rename_to);
+                if is_raw_text(&rename_to) {
+                    let children = self.clean_text_content(node, &name);
+                    return Cleaned::Nodes(vec![simple_element(name, new_attrs, children)]);
+                }
+                Cleaned::Children(Wrap::Element(name, new_attrs))
+            }
+
+            Action::Replace(nodes) => {
+                self.record(|| ChangeKind::ElementReplaced { name: element_name });
+                Cleaned::Nodes(nodes.into_iter().map(content_to_node).collect())
             }
         }
     }
 }
 
-pub(crate) fn sanitize_dom(dom: &RcDom, mode: &Rules) -> Rc<Node> {
-    let new_children = clean_nodes(&dom.document.children.borrow(), mode);
+/// Text of all descendants of a node, which replaces an element nested too deep.
+fn text_content(node: &Rc<Node>) -> Vec<Rc<Node>> {
+    let mut text = String::new();
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        if let NodeData::Text { ref contents } = node.data {
+            text.push_str(&contents.borrow());
+        }
+        stack.extend(node.children.borrow().iter().rev().cloned());
+    }
+    match text.is_empty() {
+        true => vec![],
+        false => vec![Node::new(NodeData::Text {
+            contents: RefCell::new(text.into()),
+        })],
+    }
+}
 
+fn new_document(children: Vec<Rc<Node>>) -> Rc<Node> {
     let new_dom = Node::new(NodeData::Document);
-    new_dom.children.borrow_mut().extend(new_children);
+    for child in children {
+        append(&new_dom, child);
+    }
     new_dom
 }
+
+pub(crate) fn sanitize_document(
+    document: &Document,
+    mode: &Rules,
+    with_report: bool,
+) -> Result<(Document, Option<Report>), SanitizeError> {
+    let mut sanitizer = Sanitizer::new(mode, with_report);
+    let new_children = match document.context().map(str::to_ascii_lowercase) {
+        // a fragment for a raw text element is its text, which must not close the element
+        Some(context) if is_raw_text(&context) => {
+            sanitizer.clean_text_content(document.handle(), &parse::context_name(&context))
+        }
+        _ => sanitizer.clean_document(document.handle()),
+    };
+    if let Some(error) = sanitizer.error {
+        return Err(error);
+    }
+    let context = document.context().map(str::to_owned);
+    Ok((
+        Document::new(new_document(new_children), context),
+        sanitizer.report,
+    ))
+}
@@ -0,0 +1,819 @@
+//! Sanitization of a token stream, which does not build a document tree.
+//!
+//! Elements are matched by a stack of open elements instead of the tree construction algorithm,
+//! so memory use depends on nesting depth rather than on the size of a document.
+
+use crate::errors::{Limit, SanitizeError};
+use crate::parse::context_name;
+use crate::rcdom::SerializableHandle;
+use crate::report::ChangeKind;
+use crate::rules::Rules;
+use crate::rules::limits::OnLimit;
+use crate::sanitize::{
+    Action, Sanitizer, SiblingCounter, content_to_node, is_raw_text, is_structural,
+};
+use html5ever::serialize::{HtmlSerializer, Serialize, SerializeOpts, Serializer, TraversalScope};
+use html5ever::tendril::stream::Utf8LossyDecoder;
+use html5ever::tendril::{StrTendril, TendrilSink, fmt::UTF8};
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use html5ever::{Attribute, LocalName, Namespace, QualName, ns};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io::{self, BufWriter, Read, Write};
+
+/// Elements which have no content and no end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Tokenizer state which follows a start tag of an element, the way the tree builder sets it.
+fn content_kind(name: &str) -> Option<TokenSinkResult<()>> {
+    match name {
+        "script" => Some(TokenSinkResult::RawData(RawKind::ScriptData)),
+        "style" | "xmp" | "iframe" | "noembed" | "noframes" | "noscript" => {
+            Some(TokenSinkResult::RawData(RawKind::Rawtext))
+        }
+        "textarea" | "title" => Some(TokenSinkResult::RawData(RawKind::Rcdata)),
+        "plaintext" => Some(TokenSinkResult::Plaintext),
+        _ => None,
+    }
+}
+
+/// Elements which close foreign content, the way the tree builder breaks out of it.
+fn breaks_out(tag: &Tag) -> bool {
+    match &*tag.name {
+        "b" | "big" | "blockquote" | "body" | "br" | "center" | "code" | "dd" | "div" | "dl"
+        | "dt" | "em" | "embed" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "head" | "hr" | "i"
+        | "img" | "li" | "listing" | "menu" | "meta" | "nobr" | "ol" | "p" | "pre" | "ruby"
+        | "s" | "small" | "span" | "strong" | "strike" | "sub" | "sup" | "table" | "tt" | "u"
+        | "ul" | "var" => true,
+        "font" => tag
+            .attrs
+            .iter()
+            .any(|attr| matches!(&*attr.name.local, "color" | "face" | "size")),
+        _ => false,
+    }
+}
+
+/// Elements which close an open `p` element.
+fn closes_paragraph(name: &str) -> bool {
+    matches!(
+        name,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "center"
+            | "dd"
+            | "details"
+            | "dialog"
+            | "dir"
+            | "div"
+            | "dl"
+            | "dt"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "hgroup"
+            | "hr"
+            | "li"
+            | "listing"
+            | "main"
+            | "menu"
+            | "nav"
+            | "ol"
+            | "p"
+            | "plaintext"
+            | "pre"
+            | "search"
+            | "section"
+            | "summary"
+            | "ul"
+            | "xmp"
+    )
+}
+
+fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Elements which limit the search for an open `p` element to close.
+fn is_scope_boundary(name: &str) -> bool {
+    matches!(
+        name,
+        "applet"
+            | "button"
+            | "caption"
+            | "html"
+            | "marquee"
+            | "object"
+            | "table"
+            | "td"
+            | "template"
+            | "th"
+    )
+}
+
+/// Elements of the special category, which stop the search for an open list item to close,
+/// except `address`, `div` and `p`.
+fn stops_list_item(name: &str) -> bool {
+    matches!(
+        name,
+        "applet"
+            | "area"
+            | "article"
+            | "aside"
+            | "base"
+            | "basefont"
+            | "bgsound"
+            | "blockquote"
+            | "body"
+            | "br"
+            | "button"
+            | "caption"
+            | "center"
+            | "col"
+            | "colgroup"
+            | "details"
+            | "dir"
+            | "dl"
+            | "embed"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "frame"
+            | "frameset"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "head"
+            | "header"
+            | "hgroup"
+            | "hr"
+            | "html"
+            | "iframe"
+            | "img"
+            | "input"
+            | "keygen"
+            | "link"
+            | "listing"
+            | "main"
+            | "marquee"
+            | "menu"
+            | "meta"
+            | "nav"
+            | "noembed"
+            | "noframes"
+            | "noscript"
+            | "object"
+            | "ol"
+            | "param"
+            | "plaintext"
+            | "pre"
+            | "script"
+            | "search"
+            | "section"
+            | "select"
+            | "source"
+            | "style"
+            | "summary"
+            | "table"
+            | "tbody"
+            | "td"
+            | "template"
+            | "textarea"
+            | "tfoot"
+            | "th"
+            | "thead"
+            | "title"
+            | "tr"
+            | "track"
+            | "ul"
+            | "wbr"
+            | "xmp"
+    )
+}
+
+/// Foreign elements whose content is HTML again.
+fn is_integration_point(namespace: &Namespace, name: &str, attrs: &[Attribute]) -> bool {
+    if *namespace == ns!(svg) {
+        matches!(name, "foreignobject" | "desc" | "title")
+    } else if *namespace != ns!(mathml) {
+        false
+    } else if name == "annotation-xml" {
+        attrs.iter().any(|attr| {
+            &*attr.name.local == "encoding"
+                && (attr.value.eq_ignore_ascii_case("text/html")
+                    || attr.value.eq_ignore_ascii_case("application/xhtml+xml"))
+        })
+    } else {
+        matches!(name, "mi" | "mo" | "mn" | "ms" | "mtext")
+    }
+}
+
+/// How the output is parsed inside of an element.
+#[derive(Clone, PartialEq)]
+enum Context {
+    Html,
+    /// Content is text, so child elements are dropped.
+    Text,
+    /// Content consists of foreign elements of a namespace.
+    Foreign(Namespace),
+}
+
+/// Element which is open in a source document.
+struct Frame {
+    name: String,
+    /// Context of content, which is inherited from a parent unless the element is written.
+    context: Context,
+    /// Name of an element written to the output, which has to be closed.
+    output: Option<QualName>,
+    /// Content is dropped.
+    deleted: bool,
+    /// Number of items written before content of an element replaced by spaces.
+    space: Option<usize>,
+    /// Content of an output `style` element, which is sanitized as a whole.
+    stylesheet: Option<String>,
+    /// Element is counted in the nesting depth.
+    counted: bool,
+    /// Element is an SVG or MathML element.
+    foreign: bool,
+}
+
+impl Frame {
+    fn new(name: &str, context: Context) -> Self {
+        let foreign = matches!(context, Context::Foreign(_)) || matches!(name, "svg" | "math");
+        Frame {
+            name: name.to_owned(),
+            context,
+            output: None,
+            deleted: false,
+            space: None,
+            stylesheet: None,
+            counted: false,
+            foreign,
+        }
+    }
+}
+
+struct State<'r, W: Write> {
+    sanitizer: Sanitizer<'r>,
+    serializer: HtmlSerializer<BufWriter<W>>,
+    stack: Vec<Frame>,
+    /// Sibling counters of open elements, used for paths of a report.
+    counters: Vec<SiblingCounter>,
+    /// Number of deleted elements in the stack.
+    deleted: usize,
+    /// Number of items written to the output.
+    written: usize,
+    /// Previous token was text, so the current text token continues the same node.
+    in_text: bool,
+    error: Option<SanitizeError>,
+}
+
+impl<W: Write> State<'_, W> {
+    fn write(&mut self, write: impl FnOnce(&mut HtmlSerializer<BufWriter<W>>) -> io::Result<()>) {
+        self.written += 1;
+        if let Err(error) = write(&mut self.serializer) {
+            self.error.get_or_insert(SanitizeError::Serialize(error));
+        }
+    }
+
+    fn push_segment(&mut self, name: String) {
+        if self.sanitizer.report.is_some() {
+            let counter = self.counters.last_mut().expect("root counter");
+            let segment = counter.named_segment(name);
+            self.sanitizer.path.push(segment);
+        }
+    }
+
+    fn pop_segment(&mut self) {
+        if self.sanitizer.report.is_some() {
+            self.sanitizer.path.pop();
+        }
+    }
+
+    fn context(&self) -> &Context {
+        self.stack
+            .last()
+            .map_or(&Context::Html, |frame| &frame.context)
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.deleted > 0 {
+            return;
+        }
+        match self.stack.last_mut() {
+            Some(Frame {
+                stylesheet: Some(stylesheet),
+                ..
+            }) => stylesheet.push_str(text),
+            _ => self.write(|serializer| serializer.write_text(text)),
+        }
+    }
+
+    fn comment(&mut self, text: &str) {
+        if self.deleted > 0 || *self.context() == Context::Text {
+            return;
+        }
+        if self.sanitizer.rules.allow_comments {
+            self.write(|serializer| serializer.write_comment(text));
+        } else {
+            self.push_segment("comment()".to_owned());
+            self.sanitizer.record(|| ChangeKind::CommentStripped {
+                text: text.to_owned(),
+            });
+            self.pop_segment();
+        }
+    }
+
+    fn start_tag(&mut self, tag: Tag) -> TokenSinkResult<()> {
+        let name = tag.name.to_string();
+        if let Context::Foreign(_) = self.context()
+            && breaks_out(&tag)
+        {
+            while let Context::Foreign(_) = self.context() {
+                self.close_element();
+            }
+        }
+        if *self.context() == Context::Html {
+            self.close_implied(&name);
+        }
+        let context = self.context().clone();
+        let kind = match context {
+            Context::Foreign(_) => None,
+            _ => content_kind(&name),
+        };
+        let is_void = VOID_ELEMENTS.contains(&name.as_str()) || tag.self_closing && kind.is_none();
+
+        self.push_segment(name.clone());
+        let mut frame = Frame::new(&name, context);
+        if self.deleted > 0 {
+            frame.deleted = true;
+        } else if frame.context == Context::Text {
+            // a tag written into text could end it and start markup
+            self.sanitizer
+                .record(|| ChangeKind::ElementDropped { name: name.clone() });
+            frame.deleted = true;
+        } else {
+            frame.counted = !is_structural(&name);
+            if frame.counted {
+                self.sanitizer.depth += 1;
+            }
+            let limits = &self.sanitizer.rules.limits;
+            let (max_depth, on_exceed) = (limits.max_depth, limits.on_exceed);
+            if frame.counted
+                && self
+                    .sanitizer
+                    .exceeds(Limit::Depth, max_depth, self.sanitizer.depth)
+            {
+                // a flattened element is elided, so is everything inside of it
+                frame.deleted = on_exceed != OnLimit::Flatten;
+            } else {
+                self.open_element(&mut frame, &tag.attrs);
+            }
+        }
+
+        // content of a template is not a part of a document tree, so the tree sanitizer drops it
+        if name == "template" && !frame.foreign {
+            frame.deleted = true;
+        }
+        if frame.deleted {
+            self.deleted += 1;
+        }
+        if self.sanitizer.rules.transformer.is_some() {
+            self.sanitizer.ancestors.push(name);
+        }
+        self.counters.push(SiblingCounter::default());
+        self.stack.push(frame);
+        if is_void {
+            self.close_element();
+        }
+        kind.unwrap_or(TokenSinkResult::Continue)
+    }
+
+    /// Closes elements which a start tag ends implicitly, the way the tree builder does,
+    /// e.g. an open `p` before a `div` or a previous `li` before a next one.
+    fn close_implied(&mut self, name: &str) {
+        match name {
+            "li" => self.close_list_item(&["li"]),
+            "dd" | "dt" => self.close_list_item(&["dd", "dt"]),
+            "option" => self.close_current(&["option"]),
+            "optgroup" => {
+                self.close_current(&["option"]);
+                self.close_current(&["optgroup"]);
+            }
+            "td" | "th" => {
+                self.close_in_table(&["td", "th"], &["tr", "tbody", "thead", "tfoot"]);
+            }
+            "tr" => self.close_in_table(&["tr"], &["tbody", "thead", "tfoot"]),
+            "tbody" | "thead" | "tfoot" => self.close_in_table(&["tbody", "thead", "tfoot"], &[]),
+            _ => {}
+        }
+        if closes_paragraph(name) {
+            self.close_open(|frame| match &*frame.name {
+                "p" => Some(true),
+                name if frame.foreign || is_scope_boundary(name) => Some(false),
+                _ => None,
+            });
+        }
+        if is_heading(name) {
+            self.close_current(&["h1", "h2", "h3", "h4", "h5", "h6"]);
+        }
+    }
+
+    /// Closes the current element if it is one of `names`.
+    fn close_current(&mut self, names: &[&str]) {
+        if self
+            .stack
+            .last()
+            .is_some_and(|frame| names.contains(&frame.name.as_str()))
+        {
+            self.close_element();
+        }
+    }
+
+    fn close_list_item(&mut self, names: &[&str]) {
+        self.close_open(|frame| match &*frame.name {
+            name if names.contains(&name) => Some(true),
+            "address" | "div" | "p" if !frame.foreign => None,
+            name if frame.foreign || stops_list_item(name) => Some(false),
+            _ => None,
+        });
+    }
+
+    /// Closes an open table part, unless a table or one of `stops` encloses the current element
+    /// first.
+    fn close_in_table(&mut self, names: &[&str], stops: &[&str]) {
+        self.close_open(|frame| match &*frame.name {
+            name if names.contains(&name) => Some(true),
+            "html" | "table" | "template" => Some(false),
+            name if frame.foreign || stops.contains(&name) => Some(false),
+            _ => None,
+        });
+    }
+
+    /// Looks for an open element from the current one up. `found` returns `Some(true)` for
+    /// an element to close with everything inside of it, `Some(false)` to stop the search.
+    fn close_open(&mut self, found: impl Fn(&Frame) -> Option<bool>) {
+        let index = self
+            .stack
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, frame)| found(frame).map(|close| close.then_some(index)))
+            .flatten();
+        if let Some(index) = index {
+            while self.stack.len() > index {
+                self.close_element();
+            }
+        }
+    }
+
+    fn open_element(&mut self, frame: &mut Frame, attrs: &[Attribute]) {
+        let name = frame.name.clone();
+        let attrs = self.sanitizer.limit_attributes(attrs);
+        let output_name = match self.sanitizer.element_action(&name, attrs) {
+            Action::Keep(element_sanitizer) => {
+                let attrs = self
+                    .sanitizer
+                    .clean_attributes(element_sanitizer, &name, attrs);
+                Some((name.clone(), ns!(html), attrs))
+            }
+            Action::Delete => {
+                self.sanitizer
+                    .record(|| ChangeKind::ElementDropped { name: name.clone() });
+                frame.deleted = true;
+                None
+            }
+            Action::Elide => {
+                if !is_structural(&name) {
+                    self.sanitizer
+                        .record(|| ChangeKind::ElementElided { name: name.clone() });
+                }
+                None
+            }
+            Action::Space => {
+                self.sanitizer
+                    .record(|| ChangeKind::ElementSpaced { name: name.clone() });
+                self.write(|serializer| serializer.write_text(" "));
+                frame.space = Some(self.written);
+                None
+            }
+            Action::Rename(rename_to, attributes) => {
+                self.sanitizer.record(|| ChangeKind::ElementRenamed {
+                    from: name.clone(),
+                    to: rename_to.to_string(),
+                });
+                let attrs = self
+                    .sanitizer
+                    .rename_attributes(&rename_to, &attributes, attrs);
+                Some((rename_to.into_owned(), ns!(), attrs))
+            }
+            Action::Replace(nodes) => {
+                self.sanitizer
+                    .record(|| ChangeKind::ElementReplaced { name: name.clone() });
+                for node in nodes {
+                    let node = SerializableHandle::from(content_to_node(node));
+                    self.write(|serializer| {
+                        node.serialize(serializer, TraversalScope::IncludeNode)
+                    });
+                }
+                frame.deleted = true;
+                None
+            }
+        };
+
+        if let Some((output_name, namespace, attrs)) = output_name {
+            let namespace = match (output_name.as_str(), &frame.context) {
+                ("svg", _) => ns!(svg),
+                ("math", _) => ns!(mathml),
+                (_, Context::Foreign(namespace)) => namespace.clone(),
+                _ => namespace,
+            };
+            frame.context = if namespace == ns!(svg) || namespace == ns!(mathml) {
+                match is_integration_point(&namespace, &output_name, &attrs) {
+                    true => Context::Html,
+                    false => Context::Foreign(namespace.clone()),
+                }
+            } else if is_raw_text(&output_name) {
+                Context::Text
+            } else {
+                Context::Html
+            };
+            if output_name == "style" {
+                frame.stylesheet = Some(String::new());
+            }
+            let output_name = QualName::new(None, namespace, LocalName::from(output_name));
+            self.write(|serializer| {
+                serializer.start_elem(
+                    output_name.clone(),
+                    attrs.iter().map(|attr| (&attr.name, &attr.value[..])),
+                )
+            });
+            frame.output = Some(output_name);
+        }
+    }
+
+    fn close_element(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        if let Some(stylesheet) = frame.stylesheet {
+            let output_name = frame.output.as_ref().map(|name| name.local.to_string());
+            let stylesheet = self
+                .sanitizer
+                .clean_stylesheet(&stylesheet, output_name.as_deref().unwrap_or("style"));
+            if !stylesheet.is_empty() {
+                self.write(|serializer| serializer.write_text(&stylesheet));
+            }
+        }
+        if let Some(written) = frame.space
+            && written != self.written
+        {
+            self.write(|serializer| serializer.write_text(" "));
+        }
+        if let Some(output_name) = frame.output {
+            self.write(|serializer| serializer.end_elem(output_name));
+        }
+        if frame.deleted {
+            self.deleted -= 1;
+        }
+        if frame.counted {
+            self.sanitizer.depth -= 1;
+        }
+        if self.sanitizer.rules.transformer.is_some() {
+            self.sanitizer.ancestors.pop();
+        }
+        self.counters.pop();
+        self.pop_segment();
+    }
+
+    fn end_tag(&mut self, tag: Tag) {
+        if let Some(index) = self
+            .stack
+            .iter()
+            .rposition(|frame| *frame.name == *tag.name)
+        {
+            while self.stack.len() > index {
+                self.close_element();
+            }
+        }
+    }
+
+    fn end(&mut self) {
+        while !self.stack.is_empty() {
+            self.close_element();
+        }
+        if let Err(error) = self.serializer.writer.flush() {
+            self.error.get_or_insert(SanitizeError::Serialize(error));
+        }
+    }
+
+    fn process_token(&mut self, token: Token) -> TokenSinkResult<()> {
+        let is_node = match token {
+            Token::TagToken(ref tag) => tag.kind == TagKind::StartTag && !is_structural(&tag.name),
+            Token::CharacterTokens(_) => !self.in_text,
+            Token::CommentToken(_) => true,
+            _ => false,
+        };
+        self.in_text = matches!(token, Token::CharacterTokens(_));
+        if (is_node && !self.sanitizer.count_node())
+            || (self.sanitizer.is_stopped() && token != Token::EOFToken)
+        {
+            self.take_error();
+            return TokenSinkResult::Continue;
+        }
+
+        let result = match token {
+            Token::TagToken(tag) => match tag.kind {
+                TagKind::StartTag => self.start_tag(tag),
+                TagKind::EndTag => {
+                    self.end_tag(tag);
+                    TokenSinkResult::Continue
+                }
+            },
+            Token::CharacterTokens(text) => {
+                self.text(&text);
+                TokenSinkResult::Continue
+            }
+            Token::CommentToken(text) => {
+                self.comment(&text);
+                TokenSinkResult::Continue
+            }
+            Token::EOFToken => {
+                self.end();
+                TokenSinkResult::Continue
+            }
+            Token::DoctypeToken(_) | Token::NullCharacterToken | Token::ParseError(_) => {
+                TokenSinkResult::Continue
+            }
+        };
+
+        self.take_error();
+        result
+    }
+
+    /// Stops at a limit error or, in strict mode, at the first violation.
+    fn take_error(&mut self) {
+        if let Some(error) = self.sanitizer.error.take() {
+            self.error.get_or_insert(error);
+        }
+        if let Some(ref mut report) = self.sanitizer.report
+            && !report.is_empty()
+        {
+            let changes = std::mem::take(&mut report.changes);
+            self.error.get_or_insert(SanitizeError::Policy(changes));
+        }
+    }
+}
+
+struct StreamSink<'r, W: Write> {
+    state: RefCell<State<'r, W>>,
+}
+
+impl<W: Write> TokenSink for StreamSink<'_, W> {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        let mut state = self.state.borrow_mut();
+        if state.error.is_some() {
+            return TokenSinkResult::Continue;
+        }
+        state.process_token(token)
+    }
+}
+
+/// Feeds decoded input to the tokenizer, so it can be used with [`TendrilSink::read_from`].
+struct StreamParser<'r, W: Write> {
+    tokenizer: Tokenizer<StreamSink<'r, W>>,
+    input: BufferQueue,
+}
+
+impl<W: Write> TendrilSink<UTF8> for StreamParser<'_, W> {
+    type Output = Result<(), SanitizeError>;
+
+    fn process(&mut self, tendril: StrTendril) {
+        self.input.push_back(tendril);
+        let _ = self.tokenizer.feed(&self.input);
+    }
+
+    fn error(&mut self, _desc: Cow<'static, str>) {}
+
+    fn finish(self) -> Self::Output {
+        let _ = self.tokenizer.feed(&self.input);
+        self.tokenizer.end();
+        match self.tokenizer.sink.state.into_inner().error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reader which applies the input size limit.
+struct LimitedReader<R: Read> {
+    inner: R,
+    remaining: usize,
+    /// Input is cut instead of failing.
+    truncate: bool,
+    exceeded: bool,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.exceeded {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.remaining);
+        let mut read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read;
+        if self.remaining > 0 || buf.is_empty() {
+            return Ok(read);
+        }
+        let mut next = [0];
+        if self.inner.read(&mut next)? == 0 {
+            return Ok(read);
+        }
+        self.exceeded = true;
+        if !self.truncate {
+            return Err(io::Error::other("input size limit exceeded"));
+        }
+        // cut at a character boundary, if it is inside of this chunk
+        if next[0] & 0xC0 == 0x80 {
+            while read > 0 && buf[read - 1] & 0xC0 == 0x80 {
+                read -= 1;
+            }
+            read = read.saturating_sub(1);
+        }
+        Ok(read)
+    }
+}
+
+pub(crate) fn sanitize_stream<R: Read, W: Write>(
+    rules: &Rules,
+    mut input: R,
+    output: W,
+) -> Result<(), SanitizeError> {
+    let opts = SerializeOpts {
+        scripting_enabled: false,
+        traversal_scope: TraversalScope::ChildrenOnly(Some(context_name("div"))),
+        create_missing_parent: false,
+    };
+    let state = State {
+        sanitizer: Sanitizer::new(rules, rules.strict),
+        serializer: HtmlSerializer::new(BufWriter::new(output), opts),
+        stack: Vec::new(),
+        counters: vec![SiblingCounter::default()],
+        deleted: 0,
+        written: 0,
+        in_text: false,
+        error: None,
+    };
+    let parser = StreamParser {
+        tokenizer: Tokenizer::new(
+            StreamSink {
+                state: RefCell::new(state),
+            },
+            TokenizerOpts::default(),
+        ),
+        input: BufferQueue::default(),
+    };
+    let limits = &rules.limits;
+    let Some(max) = limits.max_input_bytes else {
+        return Utf8LossyDecoder::new(parser)
+            .read_from(&mut input)
+            .map_err(SanitizeError::Parse)?;
+    };
+    let mut input = LimitedReader {
+        inner: input,
+        remaining: max,
+        truncate: !rules.strict && limits.on_exceed != OnLimit::Fail,
+        exceeded: false,
+    };
+    let result = Utf8LossyDecoder::new(parser).read_from(&mut input);
+    match result {
+        Err(_) if input.exceeded => Err(SanitizeError::LimitExceeded {
+            limit: Limit::InputSize,
+            max,
+        }),
+        result => result.map_err(SanitizeError::Parse)?,
+    }
+}
@@ -1,8 +1,22 @@
 #![cfg(test)]
 
+use super::dom::{Document, Node, NodeKind};
+use super::errors::{Limit, SanitizeError};
+use super::report::{Change, ChangeKind, Rejection, Violation};
+use super::rules::action::{Content, ElementAction};
+use super::rules::class::ClassPolicy;
+use super::rules::css::CssRules;
+use super::rules::limits::{Limits, OnLimit};
+use super::rules::link::LinkPolicy;
+use super::rules::pattern::{AttributeRule, Pattern};
 use super::rules::predefined::*;
-use super::rules::{Element, Rules};
-use super::sanitize_str;
+use super::rules::url::{Url, UrlPolicy};
+use super::rules::{Element, MandatoryMode, RenameAttributes, RuleScope, Rules};
+use super::{
+    sanitize_dom, sanitize_fragment, sanitize_str, sanitize_stream, sanitize_with_report,
+    serialize, validate,
+};
+use regex::Regex;
 
 #[test]
 fn empty() {
@@ -37,7 +51,7 @@ fn basic_restricted() {
 fn basic_untrusted() {
     assert_eq!(
         &sanitize_str(&UNTRUSTED, BASIC_HTML).unwrap(),
-        "<b>Lorem</b> <a href=\"pants\" rel=\"noreferrer noopener\" target=\"_blank\">ipsum</a> <a href=\"http://foo.com/\" rel=\"noreferrer noopener\" target=\"_blank\"><strong>dolor</strong></a> sit amet "
+        "<b>Lorem</b> <a href=\"pants\" rel=\"nofollow ugc noopener noreferrer\">ipsum</a> <a href=\"http://foo.com/\" rel=\"nofollow ugc noopener noreferrer\" target=\"_blank\"><strong>dolor</strong></a> sit amet "
     );
 }
 
@@ -81,7 +95,7 @@ fn malformed_restricted() {
 fn malformed_untrusted() {
     assert_eq!(
         &sanitize_str(&UNTRUSTED, MALFORMED_HTML).unwrap(),
-        "Lorem <a href=\"pants\" rel=\"noreferrer noopener\" target=\"_blank\"><strong>dolor</strong></a> sit amet "
+        "Lorem <a href=\"pants\" rel=\"nofollow ugc noopener noreferrer\"><strong>dolor</strong></a> sit amet "
     );
 }
 
@@ -163,7 +177,7 @@ fn malicious_basic() {
 fn malicious_untrusted() {
     assert_eq!(
         &sanitize_str(&UNTRUSTED, MALICIOUS_HTML).unwrap(),
-        "<b>Lorem</b> <a rel=\"noreferrer noopener\" target=\"_blank\">ipsum</a> <a href=\"http://foo.com/\" rel=\"noreferrer noopener\" target=\"_blank\"><strong>dolor</strong></a> sit amet &lt;script&gt;alert(\"hello world\");"
+        "<b>Lorem</b> <a>ipsum</a> <a href=\"http://foo.com/\" rel=\"nofollow ugc noopener noreferrer\" target=\"_blank\"><strong>dolor</strong></a> sit amet &lt;script&gt;alert(\"hello world\");"
     );
 }
 
@@ -663,9 +677,2425 @@ fn custom_rules() {
 #[test]
 fn test_style() {
     let input = "hello <style><!-- comment-->* {color: /*white*/#ffffff;}</style> world";
-    assert_eq!(input, sanitize_str(&BASIC, input).unwrap());
+    let sanitized = "hello <style>comment-->* { color: #ffffff }</style> world";
+    assert_eq!(sanitized, sanitize_str(&BASIC, input).unwrap());
     assert_eq!("hello  world", sanitize_str(&DEFAULT, input).unwrap());
-    assert_eq!(input, sanitize_str(&RELAXED, input).unwrap());
+    assert_eq!(sanitized, sanitize_str(&RELAXED, input).unwrap());
     assert_eq!("hello  world", sanitize_str(&RESTRICTED, input).unwrap());
     assert_eq!("hello  world", sanitize_str(&UNTRUSTED, input).unwrap());
 }
+
+/* fragment */
+
+#[test]
+fn fragment_table_cells() {
+    let input = "<td>a</td><td>b</td>";
+    assert_eq!(&sanitize_str(&RELAXED, input).unwrap(), "ab");
+    assert_eq!(
+        &sanitize_fragment(&RELAXED, "tr", input).unwrap(),
+        "<td>a</td><td>b</td>"
+    );
+}
+
+#[test]
+fn fragment_list_items() {
+    assert_eq!(
+        &sanitize_fragment(&BASIC, "ul", "<li>a<li>b").unwrap(),
+        "<li>a</li><li>b</li>"
+    );
+}
+
+#[test]
+fn fragment_textarea() {
+    assert_eq!(
+        &sanitize_fragment(&BASIC, "textarea", "<b>bold</b> &amp; </textarea><script>").unwrap(),
+        "&lt;b&gt;bold&lt;/b&gt; &amp; &lt;/textarea&gt;&lt;script&gt;"
+    );
+}
+
+#[test]
+fn fragment_raw_text_cannot_close_context() {
+    for context in [
+        "script",
+        "xmp",
+        "iframe",
+        "noembed",
+        "noframes",
+        "plaintext",
+    ] {
+        let input = format!("a</{}><img src=x onerror=alert(1)>", context.to_uppercase());
+        assert_eq!(
+            sanitize_fragment(&BASIC, context, &input).unwrap(),
+            format!(
+                "a<\\/{}><img src=x onerror=alert(1)>",
+                context.to_uppercase()
+            ),
+            "{}",
+            context
+        );
+        assert!(matches!(
+            sanitize_fragment(&BASIC.clone().strict(true), context, &input),
+            Err(SanitizeError::Policy(_))
+        ));
+    }
+}
+
+#[test]
+fn fragment_style_is_stylesheet() {
+    assert_eq!(
+        &sanitize_fragment(
+            &RELAXED,
+            "style",
+            "@import url(javascript:alert(1)); b { color: red; position: fixed }"
+        )
+        .unwrap(),
+        "b { color: red }"
+    );
+    assert_eq!(
+        &sanitize_fragment(&RELAXED, "style", "</style><img src=x onerror=alert(1)>").unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn fragment_noscript_keeps_only_text() {
+    assert_eq!(
+        &sanitize_fragment(
+            &BASIC,
+            "noscript",
+            "a<b title=\"</noscript><img src=x onerror=alert(1)>\">b</b>"
+        )
+        .unwrap(),
+        "a"
+    );
+}
+
+#[test]
+fn fragment_body_is_elided() {
+    assert_eq!(
+        &sanitize_fragment(&BASIC, "div", "<body><b>x</b></body>").unwrap(),
+        "<b>x</b>"
+    );
+}
+
+/* errors */
+
+#[test]
+fn error_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<SanitizeError>();
+}
+
+#[test]
+fn error_keeps_source() {
+    use std::error::Error;
+
+    let utf8_error = String::from_utf8(vec![0xff]).unwrap_err();
+    let error = SanitizeError::Utf8(utf8_error);
+    assert!(error.source().is_some());
+    assert!(
+        error
+            .to_string()
+            .starts_with("sanitized document is not valid UTF-8")
+    );
+
+    let error = SanitizeError::LimitExceeded {
+        limit: Limit::Depth,
+        max: 10,
+    };
+    assert!(error.source().is_none());
+    assert_eq!(error.to_string(), "nesting depth exceeds the limit of 10");
+}
+
+/* report */
+
+#[test]
+fn report_lists_changes() {
+    let (output, report) = sanitize_with_report(&UNTRUSTED, BASIC_HTML).unwrap();
+    assert_eq!(output, sanitize_str(&UNTRUSTED, BASIC_HTML).unwrap());
+
+    let changes: Vec<String> = report.changes.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+        changes,
+        vec![
+            "/html[1]/body[1]/b[1]/comment()[1]: comment removed",
+            "/html[1]/body[1]/a[1]: attribute title=\"foo\" removed from <a>: not allowed",
+            "/html[1]/body[1]/a[1]: attribute rel=\"nofollow ugc noopener noreferrer\" added to <a>",
+            "/html[1]/body[1]/a[2]: attribute rel=\"nofollow ugc noopener noreferrer\" added to <a>",
+            "/html[1]/body[1]/a[2]: attribute target=\"_blank\" added to <a>",
+            "/html[1]/body[1]/br[1]: element <br> replaced with spaces",
+            "/html[1]/body[1]/script[1]: element <script> removed with its content",
+        ]
+    );
+}
+
+#[test]
+fn report_pattern_mismatch() {
+    let (_, report) = sanitize_with_report(&BASIC, JS_INJECTION_HTML_1).unwrap();
+    assert_eq!(
+        report.changes,
+        vec![Change {
+            path: "/html[1]/body[1]/a[1]".to_owned(),
+            kind: ChangeKind::AttributeRejected {
+                element: "a".to_owned(),
+                name: "href".to_owned(),
+                value: "javascript:alert('XSS');".to_owned(),
+                reason: Rejection::PatternMismatch {
+                    scope: RuleScope::Element,
+                    rule: "href".to_owned(),
+                    pattern: Some(
+                        "URL (schemes: ftp, http, https, mailto; relative; protocol-relative)"
+                            .to_owned()
+                    ),
+                },
+            },
+        }]
+    );
+}
+
+#[test]
+fn report_is_empty_for_clean_input() {
+    let (_, report) = sanitize_with_report(&BASIC, "<p>a <b>b</b></p><p>c</p>").unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn report_renamed_and_elided() {
+    let rules = Rules::new().rename("strong", "b");
+    let (output, report) = sanitize_with_report(&rules, "<div><strong>x</strong></div>").unwrap();
+    assert_eq!(output, "<b>x</b>");
+    let kinds: Vec<ChangeKind> = report.changes.into_iter().map(|c| c.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ChangeKind::ElementElided {
+                name: "div".to_owned()
+            },
+            ChangeKind::ElementRenamed {
+                from: "strong".to_owned(),
+                to: "b".to_owned()
+            },
+        ]
+    );
+}
+
+/* strict */
+
+fn violations(result: Result<(), SanitizeError>) -> Vec<Violation> {
+    match result {
+        Err(SanitizeError::Policy(violations)) => violations,
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn validate_accepts_clean_input() {
+    assert!(validate(&BASIC, "<p>a <b>b</b></p><p>c</p>").is_ok());
+    assert!(validate(&RELAXED, "<p>a <b>b</b></p><p>c</p>").is_ok());
+}
+
+#[test]
+fn validate_rejects_unsafe_input() {
+    let violations = violations(validate(&BASIC, MALICIOUS_HTML));
+    let kinds: Vec<&ChangeKind> = violations.iter().map(|v| &v.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ChangeKind::CommentStripped {
+                text: " comment ".to_owned()
+            },
+            &ChangeKind::AttributeRejected {
+                element: "a".to_owned(),
+                name: "href".to_owned(),
+                value: "javascript:pants".to_owned(),
+                reason: Rejection::PatternMismatch {
+                    scope: RuleScope::Element,
+                    rule: "href".to_owned(),
+                    pattern: Some(
+                        "URL (schemes: ftp, http, https, mailto; relative; protocol-relative)"
+                            .to_owned()
+                    ),
+                },
+            },
+            &ChangeKind::AttributeRejected {
+                element: "a".to_owned(),
+                name: "title".to_owned(),
+                value: "foo".to_owned(),
+                reason: Rejection::NotAllowed,
+            },
+            &ChangeKind::ElementElided {
+                name: "foo".to_owned()
+            },
+        ]
+    );
+}
+
+#[test]
+fn strict_returns_input_unchanged() {
+    let rules = Rules::new()
+        .strict(true)
+        .element(Element::new("b"))
+        .element(Element::new("br"));
+    let input = "<b>a</b><br/>b &amp; c";
+    assert_eq!(&sanitize_str(&rules, input).unwrap(), input);
+    assert_eq!(&sanitize_fragment(&rules, "div", input).unwrap(), input);
+}
+
+#[test]
+fn strict_rejects_input() {
+    let rules = Rules::new().strict(true).element(Element::new("b"));
+    match sanitize_str(&rules, "<b>a</b><i>b</i>") {
+        Err(SanitizeError::Policy(violations)) => {
+            assert_eq!(violations.len(), 1);
+            assert_eq!(violations[0].path, "/html[1]/body[1]/i[1]");
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/* global attributes */
+
+#[test]
+fn global_attributes() {
+    let rules = Rules::new()
+        .global_attribute("title", Pattern::any())
+        .global_attribute("lang", Pattern::regex(Regex::new("^[a-z]{2}$").unwrap()))
+        .element(Element::new("b"))
+        .element(Element::new("i").attribute("lang", Pattern::any()));
+
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<b title=\"t\" lang=\"en\" id=\"x\">a</b><b lang=\"english\">b</b><i lang=\"english\">c</i><u title=\"t\">d</u>"
+        )
+        .unwrap(),
+        "<b title=\"t\" lang=\"en\">a</b><b>b</b><i lang=\"english\">c</i>d"
+    );
+}
+
+#[test]
+fn element_attribute_overrides_global() {
+    let rules = Rules::new()
+        .global_attribute("title", Pattern::any())
+        .element(Element::new("b").attribute("title", !Pattern::any()));
+    assert_eq!(
+        &sanitize_str(&rules, "<b title=\"t\">a</b>").unwrap(),
+        "<b>a</b>"
+    );
+}
+
+#[test]
+fn element_is_valid_ignores_globals() {
+    let rules = Rules::new()
+        .global_attribute("title", Pattern::any())
+        .element(Element::new("b"));
+    let b = &rules.allowed_elements["b"];
+    assert!(!b.is_valid("title", "t"));
+    assert!(rules.is_valid_attribute(b, "title", "t"));
+}
+
+#[test]
+fn relaxed_style_keeps_only_media() {
+    assert_eq!(
+        &sanitize_str(
+            &RELAXED,
+            "<style class=\"c\" media=\"print\" title=\"t\"></style>"
+        )
+        .unwrap(),
+        "<style media=\"print\"></style>"
+    );
+}
+
+/* wildcard attributes */
+
+#[test]
+fn wildcard_attributes() {
+    let rules = Rules::new()
+        .global_attribute_prefix("aria-", Pattern::any())
+        .element(
+            Element::new("div")
+                .attribute("data-id", Pattern::regex(Regex::new("^[0-9]+$").unwrap()))
+                .attribute_prefix("data-", Pattern::any())
+                .attribute_wildcard("x-?", Pattern::any()),
+        );
+
+    assert_eq!(
+        &sanitize_fragment(
+            &rules,
+            "body",
+            "<div data-id=\"1\" data-foo=\"bar\" aria-label=\"l\" x-a=\"1\" x-ab=\"2\" id=\"x\"></div>"
+        )
+        .unwrap(),
+        "<div data-id=\"1\" data-foo=\"bar\" aria-label=\"l\" x-a=\"1\"></div>"
+    );
+
+    // exact name wins over a prefix
+    assert_eq!(
+        &sanitize_fragment(&rules, "body", "<div data-id=\"x\"></div>").unwrap(),
+        "<div></div>"
+    );
+}
+
+#[test]
+fn wildcard_attribute_in_report() {
+    let rules = Rules::new().element(
+        Element::new("div")
+            .attribute_prefix("data-", Pattern::regex(Regex::new("^[a-z]*$").unwrap())),
+    );
+    let violations = violations(validate(&rules, "<div data-x=\"1\"></div>"));
+    assert_eq!(
+        violations[0].kind.to_string(),
+        "attribute data-x=\"1\" removed from <div>: value does not match element rule `data-*`: regex /^[a-z]*$/"
+    );
+}
+
+/* style attribute */
+
+#[test]
+fn style_attribute() {
+    let rules = Rules::new()
+        .element(
+            Element::new("p").style(
+                CssRules::new()
+                    .property("color", Pattern::any())
+                    .property("background-image", Pattern::any())
+                    .url_scheme("https"),
+            ),
+        )
+        .element(Element::new("b"));
+
+    let input = "<p style=\"color: red; background-image: url(javascript:alert(1)); position: fixed\">a</p><p style=\"behavior: url(x.htc)\">b</p><b style=\"color: red\">c</b>";
+    let (output, report) = sanitize_with_report(&rules, input).unwrap();
+    assert_eq!(output, "<p style=\"color: red\">a</p><p>b</p><b>c</b>");
+
+    let changes: Vec<String> = report.changes.iter().map(|c| c.kind.to_string()).collect();
+    assert_eq!(
+        changes,
+        vec![
+            "declaration `background-image: url(javascript:alert(1))` removed from <p>",
+            "declaration `position: fixed` removed from <p>",
+            "declaration `behavior: url(x.htc)` removed from <p>",
+            "attribute style=\"color: red\" removed from <b>: not allowed",
+        ]
+    );
+}
+
+#[test]
+fn test_style_unsafe_rules() {
+    let input = "<style>@import url(https://evil.example/x.css); body { background: url(javascript:alert(1)); color: red } p { position: fixed }</style>";
+    let (output, report) = sanitize_with_report(&BASIC, input).unwrap();
+    assert_eq!(output, "<style>body { color: red }</style>");
+
+    let changes: Vec<String> = report.changes.iter().map(|c| c.kind.to_string()).collect();
+    assert_eq!(
+        changes,
+        vec![
+            "rule `@import url(https://evil.example/x.css)` removed from <style>",
+            "declaration `background: url(javascript:alert(1))` removed from <style>",
+            "declaration `position: fixed` removed from <style>",
+        ]
+    );
+}
+
+#[test]
+fn test_style_scope() {
+    let rules = Rules::new().element(Element::new("style")).stylesheet(
+        CssRules::new()
+            .property("color", Pattern::any())
+            .scope(".user"),
+    );
+    assert_eq!(
+        sanitize_str(&rules, "<style>a, p > b { color: red }</style>").unwrap(),
+        "<style>.user a, .user p > b { color: red }</style>"
+    );
+}
+
+#[test]
+fn test_style_scope_strict() {
+    let rules = Rules::new()
+        .element(Element::new("style"))
+        .stylesheet(
+            CssRules::new()
+                .property("color", Pattern::any())
+                .scope(".post"),
+        )
+        .strict(true);
+    let input = "<style>body { color: red }</style>";
+    let violation = Change {
+        path: "/html[1]/head[1]/style[1]".to_owned(),
+        kind: ChangeKind::StylesheetChanged {
+            element: "style".to_owned(),
+            from: "body { color: red }".to_owned(),
+            to: ".post body { color: red }".to_owned(),
+        },
+    };
+    assert_eq!(violations(validate(&rules, input)), vec![violation.clone()]);
+    match sanitize_str(&rules, input) {
+        Err(SanitizeError::Policy(violations)) => assert_eq!(violations, vec![violation]),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+/* URL attributes */
+
+#[test]
+fn url_obfuscated_schemes() {
+    for href in [
+        "JaVaScRiPt:alert(1)",
+        "java&#x09;script:alert(1)",
+        "java&#x0A;script:alert(1)",
+        "&#x01;&#x20;javascript:alert(1)",
+        "jav&#x0D;ascript:alert(1)",
+        "vbscript:msgbox(1)",
+        "data:text/html,<script>alert(1)</script>",
+    ] {
+        let input = format!("<a href=\"{}\">x</a>", href);
+        assert_eq!(&sanitize_str(&BASIC, &input).unwrap(), "<a>x</a>");
+    }
+}
+
+#[test]
+fn url_allowed_values() {
+    assert_eq!(
+        &sanitize_str(&BASIC, "<a href=\" HTTPS://example.com/ \">x</a>").unwrap(),
+        "<a href=\" HTTPS://example.com/ \">x</a>"
+    );
+    assert_eq!(
+        &sanitize_str(
+            &RELAXED,
+            "<img src=\"//example.com/a.png\"><img src=\"a.png?x=1:2\">"
+        )
+        .unwrap(),
+        "<img src=\"//example.com/a.png\"><img src=\"a.png?x=1:2\">"
+    );
+    assert_eq!(
+        &sanitize_str(&RELAXED, "<img src=\"ftp://example.com/a.png\">").unwrap(),
+        "<img>"
+    );
+}
+
+#[test]
+fn url_hosts() {
+    let rules = Rules::new().element(Element::new("iframe").attribute(
+        "src",
+        Pattern::url(UrlPolicy::new().scheme("https").host_suffix("youtube.com")),
+    ));
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<iframe src=\"https://www.youtube.com/embed/x\"></iframe>"
+        )
+        .unwrap(),
+        "<iframe src=\"https://www.youtube.com/embed/x\"></iframe>"
+    );
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<iframe src=\"https://youtube.com.evil.com/\"></iframe>"
+        )
+        .unwrap(),
+        "<iframe></iframe>"
+    );
+}
+
+/* base URL */
+
+#[test]
+fn base_url_rewrites_relative_urls() {
+    let rules = Rules::new()
+        .element(Element::new("a").attribute("href", Pattern::any()))
+        .element(
+            Element::new("img")
+                .attribute("src", Pattern::any())
+                .attribute("alt", Pattern::any()),
+        )
+        .base_url(Url::parse("https://example.com/posts/1/").unwrap());
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<a href=\"/about\">a</a><a href=\"https://other.com/\">b</a><img src=\"cat.png\" alt=\"cat.png\">"
+        )
+        .unwrap(),
+        "<a href=\"https://example.com/about\">a</a><a href=\"https://other.com/\">b</a><img src=\"https://example.com/posts/1/cat.png\" alt=\"cat.png\">"
+    );
+}
+
+#[test]
+fn base_url_after_validation() {
+    let rules = Rules::new()
+        .element(
+            Element::new("a").attribute("href", Pattern::url(UrlPolicy::new().scheme("https"))),
+        )
+        .base_url(Url::parse("https://example.com/").unwrap());
+    // relative URLs are not allowed by the pattern, so they are dropped rather than resolved
+    assert_eq!(
+        &sanitize_str(&rules, "<a href=\"/about\">a</a>").unwrap(),
+        "<a>a</a>"
+    );
+}
+
+#[test]
+fn base_url_strict() {
+    let rules = Rules::new()
+        .element(Element::new("a").attribute("href", Pattern::any()))
+        .base_url(Url::parse("https://e.com/").unwrap())
+        .strict(true);
+    let violation = Change {
+        path: "/html[1]/body[1]/a[1]".to_owned(),
+        kind: ChangeKind::AttributeChanged {
+            element: "a".to_owned(),
+            name: "href".to_owned(),
+            from: "x".to_owned(),
+            to: "https://e.com/x".to_owned(),
+        },
+    };
+    assert_eq!(
+        violations(validate(&rules, "<a href=\"x\">a</a>")),
+        vec![violation.clone()]
+    );
+    match sanitize_str(&rules, "<a href=\"x\">a</a>") {
+        Err(SanitizeError::Policy(violations)) => assert_eq!(violations, vec![violation]),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(validate(&rules, "<a href=\"https://e.com/x\">a</a>").is_ok());
+}
+
+/* attribute transformers */
+
+fn transform_rules() -> Rules {
+    Rules::new().element(
+        Element::new("td")
+            .attribute(
+                "width",
+                AttributeRule::transform(|value| {
+                    let width: u32 = value.trim().parse().ok()?;
+                    Some(width.min(500).to_string())
+                }),
+            )
+            .attribute(
+                "class",
+                AttributeRule::transform(|value| {
+                    let classes: Vec<&str> = value
+                        .split_ascii_whitespace()
+                        .filter(|class| class.starts_with("cell-"))
+                        .collect();
+                    (!classes.is_empty()).then(|| classes.join(" "))
+                }),
+            )
+            .attribute(
+                "title",
+                AttributeRule::transform(|value| Some(value.trim().to_owned())),
+            ),
+    )
+}
+
+#[test]
+fn transform_attribute_values() {
+    assert_eq!(
+        &sanitize_fragment(
+            &transform_rules(),
+            "tr",
+            "<td width=\" 800 \" class=\"big cell-a cell-b\" title=\" x \">a</td><td width=\"wide\" class=\"big\">b</td>"
+        )
+        .unwrap(),
+        "<td width=\"500\" class=\"cell-a cell-b\" title=\"x\">a</td><td>b</td>"
+    );
+}
+
+#[test]
+fn transform_report() {
+    let (output, report) = sanitize_with_report(
+        &transform_rules().element(Element::new("table").attribute("id", Pattern::any())),
+        "<table><tr><td width=\"50\" title=\"x \">a</td></tr></table>",
+    )
+    .unwrap();
+    assert_eq!(
+        &output,
+        "<table><td width=\"50\" title=\"x\">a</td></table>"
+    );
+    assert_eq!(
+        report
+            .changes
+            .iter()
+            .filter(|change| matches!(change.kind, ChangeKind::AttributeChanged { .. }))
+            .cloned()
+            .collect::<Vec<_>>(),
+        vec![Change {
+            path: "/html[1]/body[1]/table[1]/tbody[1]/tr[1]/td[1]".to_owned(),
+            kind: ChangeKind::AttributeChanged {
+                element: "td".to_owned(),
+                name: "title".to_owned(),
+                from: "x ".to_owned(),
+                to: "x".to_owned(),
+            },
+        }]
+    );
+}
+
+/* class policy */
+
+fn class_rules() -> Rules {
+    Rules::new()
+        .element(
+            Element::new("code").class_policy(
+                ClassPolicy::new().pattern(Regex::new("^language-[a-z0-9]+$").unwrap()),
+            ),
+        )
+        .element(Element::new("span"))
+        .element(Element::new("b").attribute("class", Pattern::any()))
+        .class_policy(ClassPolicy::new().name("note").name("warning"))
+}
+
+#[test]
+fn class_policy_of_element() {
+    assert_eq!(
+        &sanitize_str(
+            &class_rules(),
+            "<code class=\"  language-rust\tnote \">a</code><code class=\"note\">b</code>"
+        )
+        .unwrap(),
+        "<code class=\"language-rust\">a</code><code>b</code>"
+    );
+}
+
+#[test]
+fn class_policy_of_rules() {
+    assert_eq!(
+        &sanitize_str(
+            &class_rules(),
+            "<span class=\"warning evil note\">a</span><b class=\"evil\">b</b>"
+        )
+        .unwrap(),
+        "<span class=\"warning note\">a</span><b class=\"evil\">b</b>"
+    );
+}
+
+#[test]
+fn class_policy_report() {
+    let (_, report) =
+        sanitize_with_report(&class_rules(), "<span class=\"evil\">a</span>").unwrap();
+    assert_eq!(
+        report.changes,
+        vec![Change {
+            path: "/html[1]/body[1]/span[1]".to_owned(),
+            kind: ChangeKind::AttributeRejected {
+                element: "span".to_owned(),
+                name: "class".to_owned(),
+                value: "evil".to_owned(),
+                reason: Rejection::NoAllowedClasses,
+            },
+        }]
+    );
+}
+
+/* mandatory attributes */
+
+fn mandatory_rules(mode: MandatoryMode) -> Rules {
+    Rules::new().element(
+        Element::new("a")
+            .attribute("rel", Pattern::regex(Regex::new("^[a-z ]*$").unwrap()))
+            .attribute("href", Pattern::any())
+            .mandatory_attribute_mode("rel", "nofollow noopener", mode),
+    )
+}
+
+#[test]
+fn mandatory_override() {
+    let rules = mandatory_rules(MandatoryMode::Override);
+    for (input, output) in [
+        (
+            "<a rel=\"me\" href=\"/\">a</a>",
+            "<a rel=\"nofollow noopener\" href=\"/\">a</a>",
+        ),
+        (
+            "<a rel=\"ME\" href=\"/\">a</a>",
+            "<a href=\"/\" rel=\"nofollow noopener\">a</a>",
+        ),
+        ("<a>a</a>", "<a rel=\"nofollow noopener\">a</a>"),
+    ] {
+        assert_eq!(&sanitize_str(&rules, input).unwrap(), output);
+    }
+    let (_, report) = sanitize_with_report(&rules, "<a rel=\"me\">a</a>").unwrap();
+    assert_eq!(
+        report.changes[0].kind,
+        ChangeKind::AttributeChanged {
+            element: "a".to_owned(),
+            name: "rel".to_owned(),
+            from: "me".to_owned(),
+            to: "nofollow noopener".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn mandatory_append_tokens() {
+    let rules = mandatory_rules(MandatoryMode::AppendTokens);
+    for (input, output) in [
+        (
+            "<a rel=\"me nofollow\">a</a>",
+            "<a rel=\"me nofollow noopener\">a</a>",
+        ),
+        (
+            "<a rel=\"noopener  nofollow\">a</a>",
+            "<a rel=\"noopener  nofollow\">a</a>",
+        ),
+        ("<a rel=\"\">a</a>", "<a rel=\"nofollow noopener\">a</a>"),
+        ("<a rel=\"ME\">a</a>", "<a rel=\"nofollow noopener\">a</a>"),
+    ] {
+        assert_eq!(&sanitize_str(&rules, input).unwrap(), output);
+    }
+    assert!(validate(&rules.strict(true), "<a rel=\"nofollow noopener me\">a</a>").is_ok());
+}
+
+#[test]
+fn mandatory_set_if_missing() {
+    let rules = mandatory_rules(MandatoryMode::SetIfMissing);
+    for (input, output) in [
+        ("<a rel=\"me\">a</a>", "<a rel=\"me\">a</a>"),
+        ("<a rel=\"ME\">a</a>", "<a rel=\"nofollow noopener\">a</a>"),
+        ("<a>a</a>", "<a rel=\"nofollow noopener\">a</a>"),
+    ] {
+        assert_eq!(&sanitize_str(&rules, input).unwrap(), output);
+    }
+}
+
+#[test]
+fn mandatory_without_attribute_rule() {
+    // a source value which is not allowed is removed, so it never conflicts
+    for mode in [
+        MandatoryMode::Override,
+        MandatoryMode::AppendTokens,
+        MandatoryMode::SetIfMissing,
+    ] {
+        let rules = Rules::new()
+            .element(Element::new("a").mandatory_attribute_mode("target", "_blank", mode));
+        assert_eq!(
+            &sanitize_str(&rules, "<a target=\"_self\">a</a>").unwrap(),
+            "<a target=\"_blank\">a</a>"
+        );
+    }
+}
+
+#[test]
+fn mandatory_stream_matches_tree() {
+    for mode in [
+        MandatoryMode::Override,
+        MandatoryMode::AppendTokens,
+        MandatoryMode::SetIfMissing,
+    ] {
+        let rules = mandatory_rules(mode);
+        for input in [
+            "<a rel=\"me\" href=\"/\">a</a>",
+            "<a rel=\"ME\" rel=\"me\">a</a>",
+            "<p><a>a</a><a rel=\"nofollow\">b</a></p>",
+        ] {
+            assert_eq!(
+                stream_str(&rules, input).unwrap(),
+                sanitize_str(&rules, input).unwrap()
+            );
+        }
+    }
+}
+
+/* link policy */
+
+fn link_rules() -> Rules {
+    BASIC.clone().link_policy(
+        LinkPolicy::user_content()
+            .allow_rel("me")
+            .allow_rel("nofollow")
+            .target_blank(true)
+            .internal_host("example.com"),
+    )
+}
+
+#[test]
+fn link_policy_merges_rel() {
+    let rules = link_rules();
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<a href=\"/a\" rel=\"Me NoFollow stylesheet\">a</a>"
+        )
+        .unwrap(),
+        "<a href=\"/a\" rel=\"Me NoFollow ugc noopener\">a</a>"
+    );
+    assert_eq!(
+        &sanitize_str(&rules, "<a href=\"/a\">a</a>").unwrap(),
+        "<a href=\"/a\" rel=\"nofollow ugc noopener\">a</a>"
+    );
+    let rules = Rules::new()
+        .element(
+            Element::new("a")
+                .attribute("href", Pattern::any())
+                .attribute("rel", Pattern::any()),
+        )
+        .link_policy(LinkPolicy::new());
+    assert_eq!(
+        &sanitize_str(&rules, "<a href=\"/a\" rel=\"me\">a</a>").unwrap(),
+        "<a href=\"/a\">a</a>"
+    );
+    // an element without `href` is not a link, so attribute rules apply to it
+    assert_eq!(
+        &sanitize_str(&rules, "<a rel=\"me\">a</a>").unwrap(),
+        "<a rel=\"me\">a</a>"
+    );
+}
+
+#[test]
+fn link_policy_target_for_external_links() {
+    let rules = link_rules();
+    for (href, target, link) in [
+        ("https://other.com/", true, true),
+        ("//other.com/", true, true),
+        ("https://example.com/", false, true),
+        ("/about", false, true),
+        ("mailto:user@other.com", false, true),
+        ("javascript:alert(1)", false, false),
+    ] {
+        let output = sanitize_str(
+            &rules,
+            &format!("<a href=\"{}\" target=\"_self\">a</a>", href),
+        )
+        .unwrap();
+        assert_eq!(output.contains("target=\"_blank\""), target, "{}", output);
+        assert!(output.matches("target=").count() <= 1, "{}", output);
+        assert_eq!(output.contains("rel="), link, "{}", output);
+    }
+    assert_eq!(
+        &sanitize_str(&UNTRUSTED, "<a href=\"/about\" rel=\"me\">a</a>").unwrap(),
+        "<a href=\"/about\" rel=\"me nofollow ugc noopener noreferrer\">a</a>"
+    );
+    assert_eq!(
+        &sanitize_str(
+            &UNTRUSTED,
+            "<a href=\"https://other.com/\" rel=\"me opener\">a</a>"
+        )
+        .unwrap(),
+        "<a href=\"https://other.com/\" rel=\"me nofollow ugc noopener noreferrer\" target=\"_blank\">a</a>"
+    );
+    assert_eq!(
+        &sanitize_str(&UNTRUSTED, "<a name=\"x\">a</a>").unwrap(),
+        "<a>a</a>"
+    );
+}
+
+#[test]
+fn link_policy_report() {
+    let rules = Rules::new()
+        .element(Element::new("a").attribute("href", Pattern::any()))
+        .element(Element::new("b"))
+        .link_policy(
+            LinkPolicy::new()
+                .rel("nofollow")
+                .allow_rel("me")
+                .target_blank(true),
+        );
+    let (_, report) = sanitize_with_report(
+        &rules,
+        "<a href=\"https://a.com/\" rel=\"me\" target=\"top\">a</a><a rel=\"tag\">b</a><b rel=\"tag\">c</b>",
+    )
+    .unwrap();
+    let messages: Vec<String> = report.changes.iter().map(|c| c.kind.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "attribute rel of <a> changed from \"me\" to \"me nofollow\"",
+            "attribute target of <a> changed from \"top\" to \"_blank\"",
+            "attribute rel=\"tag\" removed from <a>: not allowed",
+            "attribute rel=\"tag\" removed from <b>: not allowed",
+        ]
+    );
+
+    let rules = rules.strict(true);
+    assert!(
+        validate(
+            &rules,
+            "<a href=\"https://a.com/\" rel=\"nofollow\" target=\"_blank\">a</a>"
+        )
+        .is_ok()
+    );
+    let violations = violations(validate(&rules, "<a href=\"/a\">a</a>"));
+    assert_eq!(
+        violations[0].kind,
+        ChangeKind::AttributeAdded {
+            element: "a".to_owned(),
+            name: "rel".to_owned(),
+            value: "nofollow".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn link_policy_stream_matches_tree() {
+    let rules = link_rules();
+    for input in [
+        "<a href=\"https://other.com/\" rel=\"me tag\" target=\"x\">a</a>",
+        "<p><a href=\"/a\" rel=\"nofollow\">a</a><area href=\"//other.com/\"></p>",
+        JS_INJECTION_HTML_1,
+        MALICIOUS_HTML,
+    ] {
+        assert_eq!(
+            stream_str(&rules, input).unwrap(),
+            sanitize_str(&rules, input).unwrap()
+        );
+    }
+}
+
+/* element transformer */
+
+#[test]
+fn transformer_sees_ancestors() {
+    let rules = Rules::new()
+        .element(Element::new("p"))
+        .element(Element::new("b"))
+        .transformer(|element| {
+            (element.name == "b" && element.ancestors.iter().any(|name| name == "blockquote"))
+                .then_some(ElementAction::Delete)
+        });
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<p><b>a</b></p><blockquote><p><b>b</b>c</p></blockquote>"
+        )
+        .unwrap(),
+        "<p><b>a</b></p><p>c</p>"
+    );
+}
+
+#[test]
+fn transformer_actions() {
+    let rules = Rules::new()
+        .element(Element::new("b"))
+        .global_attribute("title", Pattern::any())
+        .transformer(|element| match element.name {
+            "custom" => Some(ElementAction::Keep),
+            "big" => Some(ElementAction::Rename("b".to_owned())),
+            "b" => Some(ElementAction::Space),
+            "hr" => Some(ElementAction::Replace(vec![Content::text("---")])),
+            _ => None,
+        });
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<custom title=\"t\" id=\"x\">a</custom><big>b</big><b>c</b><hr><i>d</i>"
+        )
+        .unwrap(),
+        "<custom title=\"t\">a</custom><b>b</b> c ---d"
+    );
+}
+
+#[test]
+fn transformer_report() {
+    let rules = Rules::new().transformer(|element| {
+        (element.name == "hr").then(|| ElementAction::Replace(vec![Content::text("---")]))
+    });
+    let (output, report) = sanitize_with_report(&rules, "a<hr>b").unwrap();
+    assert_eq!(&output, "a---b");
+    assert_eq!(
+        report.changes,
+        vec![Change {
+            path: "/html[1]/body[1]/hr[1]".to_owned(),
+            kind: ChangeKind::ElementReplaced {
+                name: "hr".to_owned()
+            },
+        }]
+    );
+}
+
+/* rename with attributes */
+
+fn font_to_span(attributes: &[(String, String)]) -> Vec<(String, String)> {
+    let mut style = Vec::new();
+    let mut mapped = Vec::new();
+    for (name, value) in attributes {
+        match name.as_str() {
+            "color" => style.push(format!("color: {}", value)),
+            "face" => style.push(format!("font-family: {}", value)),
+            _ => mapped.push((name.clone(), value.clone())),
+        }
+    }
+    if !style.is_empty() {
+        mapped.push(("style".to_owned(), style.join("; ")));
+    }
+    mapped
+}
+
+fn rename_rules() -> Rules {
+    Rules::new()
+        .element(Element::new("strong").attribute("title", Pattern::any()))
+        .element(
+            Element::new("span")
+                .attribute("title", Pattern::any())
+                .style(
+                    CssRules::new()
+                        .property("color", Pattern::regex(Regex::new("^[a-z]+$").unwrap()))
+                        .property("font-family", Pattern::any()),
+                ),
+        )
+        .element(Element::new("div").mandatory_attribute("class", "centered"))
+        .rename_keeping_attributes("b", "strong")
+        .rename_mapping_attributes("font", "span", font_to_span)
+        .rename_keeping_attributes("center", "div")
+        .rename_keeping_attributes("blink", "marquee")
+        .rename("i", "strong")
+}
+
+#[test]
+fn rename_keeps_attributes() {
+    let rules = rename_rules();
+    for (input, output) in [
+        (
+            "<b title=\"t\" onclick=\"go()\">a</b>",
+            "<strong title=\"t\">a</strong>",
+        ),
+        ("<i title=\"t\">a</i>", "<strong>a</strong>"),
+        (
+            "<center align=\"left\" class=\"x\">a</center>",
+            "<div class=\"centered\">a</div>",
+        ),
+        ("<blink title=\"t\">a</blink>", "<marquee>a</marquee>"),
+    ] {
+        assert_eq!(&sanitize_str(&rules, input).unwrap(), output);
+    }
+}
+
+#[test]
+fn rename_maps_attributes() {
+    let rules = rename_rules();
+    for (input, output) in [
+        (
+            "<font color=\"red\" face=\"serif\" size=\"7\" title=\"t\">a</font>",
+            "<span title=\"t\" style=\"color: red; font-family: serif\">a</span>",
+        ),
+        (
+            "<font color=\"expression(alert(1))\">a</font>",
+            "<span>a</span>",
+        ),
+    ] {
+        assert_eq!(&sanitize_str(&rules, input).unwrap(), output);
+    }
+    let (_, report) =
+        sanitize_with_report(&rules, "<font size=\"7\" color=\"red\">a</font>").unwrap();
+    let messages: Vec<String> = report.changes.iter().map(|c| c.kind.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "element <font> renamed to <span>",
+            "attribute size=\"7\" removed from <span>: not allowed",
+        ]
+    );
+}
+
+#[test]
+fn rename_reports_removed_attributes() {
+    let rules = rename_rules().rename("u", "div");
+    assert_eq!(
+        &sanitize_str(&rules, "<u title=\"t\">a</u>").unwrap(),
+        "<div class=\"centered\">a</div>"
+    );
+    for (input, messages) in [
+        (
+            "<u title=\"t\">a</u>",
+            vec![
+                "element <u> renamed to <div>",
+                "attribute title=\"t\" removed from <div>: not allowed",
+                "attribute class=\"centered\" added to <div>",
+            ],
+        ),
+        (
+            "<blink title=\"t\">a</blink>",
+            vec![
+                "element <blink> renamed to <marquee>",
+                "attribute title=\"t\" removed from <marquee>: not allowed",
+            ],
+        ),
+    ] {
+        let (_, report) = sanitize_with_report(&rules, input).unwrap();
+        let changes: Vec<String> = report.changes.iter().map(|c| c.kind.to_string()).collect();
+        assert_eq!(changes, messages);
+        assert_eq!(
+            violations(validate(&rules, input))
+                .iter()
+                .map(|v| v.kind.to_string())
+                .collect::<Vec<_>>(),
+            messages
+        );
+    }
+}
+
+#[test]
+fn transformer_renames_with_attributes() {
+    let rules = Rules::new()
+        .element(Element::new("strong").attribute("title", Pattern::any()))
+        .transformer(|element| {
+            (element.name == "b")
+                .then(|| ElementAction::RenameWith("strong".to_owned(), RenameAttributes::Keep))
+        });
+    assert_eq!(
+        &sanitize_str(&rules, "<b title=\"t\" id=\"x\">a</b>").unwrap(),
+        "<strong title=\"t\">a</strong>"
+    );
+}
+
+#[test]
+fn rename_stream_matches_tree() {
+    let rules = rename_rules().rename("u", "div");
+    for input in [
+        "<div><b title=\"t\">a</b><font color=\"red\" size=\"1\">b</font><center align=\"c\">c</center></div>",
+        "<i title=\"t\">a</i><blink title=\"t\">b</blink><u title=\"t\">c</u>",
+    ] {
+        assert_eq!(
+            stream_str(&rules, input).unwrap(),
+            sanitize_str(&rules, input).unwrap()
+        );
+    }
+}
+
+/* document API */
+
+#[test]
+fn dom_transform_between_steps() {
+    let document = Document::parse("<p>a<b>b</b></p><iframe></iframe>");
+    let sanitized = sanitize_dom(&BASIC, &document).unwrap();
+
+    let root = sanitized.root();
+    let p = root.children()[0].clone();
+    assert_eq!(p.parent(), Some(root));
+    p.children()[1].detach();
+    assert_eq!(serialize(&sanitized).unwrap(), "<p>a</p>");
+
+    // source document is not shared with the result
+    assert_eq!(
+        serialize(&document).unwrap(),
+        "<html><head></head><body><p>a<b>b</b></p><iframe></iframe></body></html>"
+    );
+}
+
+#[test]
+fn dom_nodes() {
+    let document = Document::parse_fragment("div", "<p title=\"t\">a<!--c--></p>");
+    let p = document.root().children()[0].clone();
+    assert_eq!(p.kind(), NodeKind::Element);
+    assert_eq!(p.name(), Some("p"));
+    assert_eq!(p.namespace(), Some("http://www.w3.org/1999/xhtml"));
+    assert_eq!(p.attributes(), vec![("title".to_owned(), "t".to_owned())]);
+
+    assert!(p.set_attribute("title", "u"));
+    assert!(p.set_attribute("id", "x"));
+    assert_eq!(p.attribute("title").as_deref(), Some("u"));
+    assert_eq!(p.remove_attribute("id").as_deref(), Some("x"));
+    assert_eq!(p.remove_attribute("id"), None);
+
+    let children = p.children();
+    assert_eq!(children[0].kind(), NodeKind::Text);
+    assert!(children[0].set_text("b"));
+    assert_eq!(children[1].kind(), NodeKind::Comment);
+    assert_eq!(children[1].text().as_deref(), Some("c"));
+    assert!(!children[1].set_text("d"));
+    assert!(!children[1].set_attribute("title", "t"));
+    assert_eq!(
+        serialize(&document).unwrap(),
+        "<p title=\"u\">b<!--c--></p>"
+    );
+}
+
+#[test]
+fn dom_append_keeps_tree_valid() {
+    let document = Document::parse_fragment("div", "<div><p>a</p></div><b>b</b>");
+    let root = document.root();
+    let [div, b] = &root.children()[..] else {
+        panic!("unexpected children");
+    };
+    let p = div.children()[0].clone();
+
+    // a node cannot contain itself or its ancestors
+    assert!(!p.append(&p));
+    assert!(!p.append(div));
+    assert!(!p.append(&root));
+    assert!(!p.children()[0].append(b));
+
+    // an appended node is moved from its previous parent
+    assert!(p.append(b));
+    assert_eq!(b.parent().as_ref(), Some(&p));
+    assert_eq!(root.children(), vec![div.clone()]);
+    assert_eq!(serialize(&document).unwrap(), "<div><p>a<b>b</b></p></div>");
+
+    b.detach();
+    assert_eq!(b.parent(), None);
+    assert!(root.append(&Node::new_element("hr")));
+    assert_eq!(serialize(&document).unwrap(), "<div><p>a</p></div><hr>");
+}
+
+#[test]
+fn dom_raw_text_cannot_close_element() {
+    let rules = Rules::new()
+        .element(Element::new("iframe").attribute("title", Pattern::any()))
+        .element(Element::new("script"));
+    let document =
+        Document::parse_fragment("div", "<iframe title=\"t\"></iframe><script></script>");
+    let [iframe, script] = &document.root().children()[..] else {
+        panic!("unexpected children");
+    };
+    iframe.append(&Node::new_text("</IFRAME><img src=x onerror=alert(1)>"));
+    script.append(&Node::new_text("a = \"</script>\""));
+
+    let sanitized = sanitize_dom(&rules, &document).unwrap();
+    assert_eq!(
+        serialize(&sanitized).unwrap(),
+        "<iframe title=\"t\"><\\/IFRAME><img src=x onerror=alert(1)></iframe>\
+         <script>a = \"<\\/script>\"</script>"
+    );
+    assert!(matches!(
+        sanitize_dom(&rules.strict(true), &document),
+        Err(SanitizeError::Policy(violations))
+            if matches!(violations[0].kind, ChangeKind::RawTextEscaped { .. })
+    ));
+}
+
+#[test]
+fn dom_fragment() {
+    let document = Document::parse_fragment("tr", "<td>a</td><td onclick=\"x()\">b</td>");
+    assert_eq!(document.context(), Some("tr"));
+    let sanitized = sanitize_dom(&RELAXED, &document).unwrap();
+    assert_eq!(serialize(&sanitized).unwrap(), "<td>a</td><td>b</td>");
+}
+
+#[test]
+fn dom_strict() {
+    let rules = Rules::new().element(Element::new("b")).strict(true);
+    assert!(sanitize_dom(&rules, &Document::parse("<b>a</b>")).is_ok());
+    assert!(matches!(
+        sanitize_dom(&rules, &Document::parse("<i>a</i>")),
+        Err(SanitizeError::Policy(_))
+    ));
+}
+
+/* streaming */
+
+fn stream_str(rules: &Rules, input: &str) -> Result<String, SanitizeError> {
+    let mut output = Vec::new();
+    sanitize_stream(rules, input.as_bytes(), &mut output)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// Reads one byte at a time, so tokens are split between chunks.
+struct ByteReader<'a>(&'a [u8]);
+
+impl std::io::Read for ByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.split_first() {
+            Some((&byte, rest)) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn stream_matches_tree() {
+    let inputs = [
+        BASIC_HTML,
+        MALICIOUS_HTML,
+        RAW_COMMENT_HTML,
+        UNCLOSED_HTML,
+        JS_INJECTION_HTML_1,
+        JS_INJECTION_HTML_5,
+        "<p title=\"a\">x<img src=\"a.png\" alt=\"&quot;&lt;\">y</p><ul><li>1</li><li>2</li></ul>",
+        "hello <style><!-- comment-->* {color: /*white*/#ffffff;}</style> world",
+        "<div>a<br>b</div><textarea><b>c</b></textarea><title>t</title>",
+    ];
+    for rules in [&*BASIC, &*DEFAULT, &*RELAXED, &*RESTRICTED, &*UNTRUSTED] {
+        for input in inputs {
+            assert_eq!(
+                stream_str(rules, input).unwrap(),
+                sanitize_str(rules, input).unwrap(),
+                "input: {}",
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn stream_closes_open_elements() {
+    assert_eq!(
+        &stream_str(&BASIC, "<p><b>a<i>b</b>c").unwrap(),
+        "<p><b>a<i>b</i></b>c</p>"
+    );
+    assert_eq!(&stream_str(&BASIC, "a</b></p>b").unwrap(), "ab");
+}
+
+#[test]
+fn stream_implies_end_tags() {
+    let inputs = [
+        "<p>a<p>b<ul><li>c<li>d</ul>",
+        "<ol><li>a<li>b<ol><li>c<li>d</ol><li>e</ol>",
+        "<li>a<div>b<li>c</div>",
+        "<li>a<blockquote><li>b</blockquote>",
+        "<dl><dt>a<dd>b<dt>c<dd>d</dl>",
+        "<p>a<h1>b<h2>c</h2><p>d<div>e</div>f",
+        "<table><tbody><tr><td>a<td>b<tr><th>c<td>d</tbody><tbody><tr><td>e</table>",
+        "<table><tbody><tr><td><table><tbody><tr><td>a<td>b</table><td>c</table>",
+        "<p>a<button>b<p>c</button>d",
+    ];
+    for input in inputs {
+        assert_eq!(
+            stream_str(&RELAXED, input).unwrap(),
+            sanitize_str(&RELAXED, input).unwrap(),
+            "input: {}",
+            input
+        );
+    }
+    assert_eq!(
+        &stream_str(&RELAXED, "<p>a<p>b<ul><li>c<li>d</ul>").unwrap(),
+        "<p>a</p><p>b</p><ul><li>c</li><li>d</li></ul>"
+    );
+}
+
+#[test]
+fn stream_deletes_content() {
+    let rules = Rules::new()
+        .element(Element::new("b"))
+        .delete("script")
+        .delete("object")
+        .space("div");
+    assert_eq!(
+        &stream_str(
+            &rules,
+            "<script><b>x</b></script><object><b>y</b><param></object><div></div><div><b>z</b></div>"
+        )
+        .unwrap(),
+        "  <b>z</b> "
+    );
+}
+
+#[test]
+fn stream_raw_text_in_foreign_content() {
+    let rules = Rules::new()
+        .element(Element::new("svg"))
+        .element(Element::new("style"));
+    // inside of `svg` a browser parses markup in `style`, so it has to be sanitized as markup
+    assert_eq!(
+        &stream_str(
+            &rules,
+            "<svg><style><img src=x onerror=alert(1)></style></svg>"
+        )
+        .unwrap(),
+        "<svg><style></style></svg>"
+    );
+}
+
+const MUTATION_XSS_HTML: &[&str] = &[
+    "<svg><style><a title=\"</style><img src=x onerror=alert(1)>\">",
+    "<math><style><a title=\"</style><img src=x onerror=alert(1)>\">",
+    "<svg></p><style><a id=\"</style><img src=1 onerror=alert(1)>\">",
+    "<svg><p><style><a title=\"</style><img src=x onerror=alert(1)>\">",
+    "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+    "<style><b title=\"</style><img src=x onerror=alert(1)>\"></b></style>",
+    "<noscript><p title=\"</noscript><img src=x onerror=alert(1)>\"></noscript>",
+];
+
+#[test]
+fn stream_mutation_xss() {
+    for rules in [&*BASIC, &*DEFAULT, &*RELAXED, &*RESTRICTED, &*UNTRUSTED] {
+        for input in MUTATION_XSS_HTML {
+            let tree = sanitize_str(rules, input).unwrap();
+            let stream = stream_str(rules, input).unwrap();
+            for output in [tree, stream] {
+                // a browser parses the output into content which is allowed as it is
+                assert_eq!(
+                    sanitize_str(rules, &output).unwrap().trim(),
+                    output.trim(),
+                    "input: {}",
+                    input
+                );
+                assert!(!output.contains("onerror"), "input: {}", input);
+            }
+        }
+    }
+    assert_eq!(&sanitize_str(&RELAXED, MUTATION_XSS_HTML[0]).unwrap(), "");
+    assert_eq!(
+        &stream_str(&RELAXED, MUTATION_XSS_HTML[0]).unwrap(),
+        "<style></style><img src=\"x\">\"&gt;"
+    );
+    assert_eq!(
+        &stream_str(&RELAXED, MUTATION_XSS_HTML[1]).unwrap(),
+        "<style></style><img src=\"x\">\"&gt;"
+    );
+}
+
+#[test]
+fn stream_breaks_out_of_foreign_content() {
+    let rules = RELAXED
+        .clone()
+        .element(Element::new("svg"))
+        .element(Element::new("foreignobject"));
+    assert_eq!(
+        &stream_str(
+            &rules,
+            "<svg><p><style><a title=\"</style><img src=x onerror=alert(1)>\">"
+        )
+        .unwrap(),
+        "<svg></svg><p><style></style><img src=\"x\">\"&gt;</p>"
+    );
+    assert_eq!(
+        &stream_str(
+            &rules,
+            "<svg><foreignobject><style><a title=\"</style><img src=x onerror=alert(1)>\">"
+        )
+        .unwrap(),
+        "<svg><foreignobject><style></style><img src=\"x\">\"&gt;</foreignobject></svg>"
+    );
+}
+
+#[test]
+fn stream_annotation_xml_integration_point() {
+    const MATHML: &str = "http://www.w3.org/1998/math/mathml:";
+    let mut rules = RELAXED.clone();
+    // the tree sanitizer names foreign elements with their namespace, the stream one without it
+    for name in ["math", "annotation-xml"] {
+        let element = |name: &str| Element::new(name).attribute("encoding", Pattern::any());
+        rules = rules
+            .element(element(name))
+            .element(element(&format!("{}{}", MATHML, name)));
+    }
+    for input in [
+        "<math><annotation-xml encoding=\"text/html\"><style><img src=x onerror=alert(1)></style></annotation-xml></math>",
+        "<math><annotation-xml encoding=\"APPLICATION/XHTML+XML\"><style><b>x</b></style><p>y</p></annotation-xml></math>",
+        "<math><annotation-xml encoding=\"image/svg+xml\"><b>x</b></annotation-xml></math>",
+    ] {
+        let stream = stream_str(&rules, input).unwrap();
+        assert_eq!(
+            stream,
+            sanitize_str(&rules, input).unwrap(),
+            "input: {}",
+            input
+        );
+        assert!(!stream.contains("onerror"), "input: {}", input);
+    }
+}
+
+#[test]
+fn stream_drops_template_content() {
+    let with_template = BASIC.clone().element(Element::new("template"));
+    for rules in [&*BASIC, &with_template] {
+        for input in [
+            "<template><b>x</b></template>y",
+            "<p>a<template><p>b<template>c</template></template>d</p>",
+        ] {
+            assert_eq!(
+                stream_str(rules, input).unwrap(),
+                sanitize_str(rules, input).unwrap(),
+                "input: {}",
+                input
+            );
+        }
+    }
+    assert_eq!(
+        &stream_str(&BASIC, "<template><b>x</b></template>").unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn raw_text_drops_child_elements() {
+    let rules = Rules::new()
+        .element(Element::new("xmp"))
+        .element(Element::new("b").attribute("title", Pattern::any()))
+        .rename("div", "xmp");
+    let input = "<div><b title=\"</xmp><img src=x onerror=alert(1)>\">b</b>&lt;/xmp&gt;</div>";
+    let expected = "<xmp>&lt;/xmp&gt;</xmp>";
+    assert_eq!(&sanitize_str(&rules, input).unwrap(), expected);
+    assert_eq!(&stream_str(&rules, input).unwrap(), expected);
+
+    let dropped = Change {
+        path: "/html[1]/body[1]/div[1]/b[1]".to_owned(),
+        kind: ChangeKind::ElementDropped {
+            name: "b".to_owned(),
+        },
+    };
+    let (_, report) = sanitize_with_report(&rules, input).unwrap();
+    assert!(report.changes.contains(&dropped));
+}
+
+#[test]
+fn stream_chunks() {
+    let input = "<p>Hello <b title=\"t\">w&ouml;rld</b> \u{1F600}<!-- c --></p>";
+    let mut output = Vec::new();
+    sanitize_stream(&RELAXED, ByteReader(input.as_bytes()), &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<p>Hello <b title=\"t\">wörld</b> \u{1F600}</p>"
+    );
+}
+
+#[test]
+fn stream_strict() {
+    let rules = Rules::new().element(Element::new("b")).strict(true);
+    assert!(stream_str(&rules, "<b>a</b>").is_ok());
+    match stream_str(&rules, "<b>a</b><i>b</i><u>c</u>") {
+        Err(SanitizeError::Policy(violations)) => assert_eq!(
+            violations,
+            vec![Change {
+                path: "/i[1]".to_owned(),
+                kind: ChangeKind::ElementElided {
+                    name: "i".to_owned()
+                },
+            }]
+        ),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn stream_io_errors() {
+    struct Failing;
+
+    impl std::io::Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("read"))
+        }
+    }
+
+    impl std::io::Write for Failing {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("write"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    assert!(matches!(
+        sanitize_stream(&BASIC, Failing, Vec::new()),
+        Err(SanitizeError::Parse(_))
+    ));
+    assert!(matches!(
+        sanitize_stream(&BASIC, "<b>a</b>".as_bytes(), Failing),
+        Err(SanitizeError::Serialize(_))
+    ));
+}
+
+/* limits */
+
+fn limit_rules(limits: Limits) -> Rules {
+    Rules::new()
+        .element(Element::new("div").attribute("id", Pattern::any()))
+        .element(Element::new("b"))
+        .limits(limits)
+}
+
+fn exceeded(limit: Limit, max: usize) -> impl Fn(&Result<String, SanitizeError>) -> bool {
+    move |result| matches!(result, Err(SanitizeError::LimitExceeded { limit: l, max: m }) if *l == limit && *m == max)
+}
+
+#[test]
+fn limit_input_size() {
+    let input = "<b>ab\u{e9}\u{e9}</b>";
+    let fail = limit_rules(Limits::new().max_input_bytes(8));
+    assert!(sanitize_str(&fail, "<b>a</b>").is_ok());
+    assert!(exceeded(Limit::InputSize, 8)(&sanitize_str(&fail, input)));
+    assert!(exceeded(Limit::InputSize, 8)(&stream_str(&fail, input)));
+
+    let truncate = limit_rules(
+        Limits::new()
+            .max_input_bytes(8)
+            .on_exceed(OnLimit::Truncate),
+    );
+    assert_eq!(&sanitize_str(&truncate, input).unwrap(), "<b>ab\u{e9}</b>");
+    assert_eq!(&stream_str(&truncate, input).unwrap(), "<b>ab\u{e9}</b>");
+    assert_eq!(
+        sanitize_with_report(&truncate, input).unwrap().1.changes,
+        vec![Change {
+            path: "/".to_owned(),
+            kind: ChangeKind::LimitExceeded {
+                limit: Limit::InputSize,
+                max: 8
+            },
+        }]
+    );
+}
+
+#[test]
+fn limit_depth() {
+    let input = "<div><b><div>a<b>b</b></div>c</b></div>";
+    let fail = limit_rules(Limits::new().max_depth(2));
+    assert!(sanitize_str(&fail, "<div><b>a</b></div>").is_ok());
+    assert!(exceeded(Limit::Depth, 2)(&sanitize_str(&fail, input)));
+    assert!(exceeded(Limit::Depth, 2)(&stream_str(&fail, input)));
+
+    let truncate = limit_rules(Limits::new().max_depth(2).on_exceed(OnLimit::Truncate));
+    assert_eq!(
+        &sanitize_str(&truncate, input).unwrap(),
+        "<div><b>c</b></div>"
+    );
+    assert_eq!(
+        &stream_str(&truncate, input).unwrap(),
+        "<div><b>c</b></div>"
+    );
+
+    let flatten = limit_rules(Limits::new().max_depth(2).on_exceed(OnLimit::Flatten));
+    assert_eq!(
+        &sanitize_str(&flatten, input).unwrap(),
+        "<div><b>abc</b></div>"
+    );
+    assert_eq!(
+        &stream_str(&flatten, input).unwrap(),
+        "<div><b>abc</b></div>"
+    );
+}
+
+#[test]
+fn limit_depth_of_siblings() {
+    let rules = RELAXED.clone().limits(Limits::new().max_depth(50));
+    for input in [
+        "<li>a".repeat(100),
+        format!("<ol>{}</ol>", "<li>a".repeat(100)),
+        "<p>a".repeat(100),
+        format!("<dl>{}</dl>", "<dt>a<dd>b".repeat(100)),
+        format!("<table><tbody>{}</table>", "<tr><td>a<td>b".repeat(100)),
+    ] {
+        assert_eq!(
+            stream_str(&rules, &input).unwrap(),
+            sanitize_str(&rules, &input).unwrap(),
+            "input: {}",
+            input
+        );
+    }
+    let deep = "<div>".repeat(51);
+    assert!(exceeded(Limit::Depth, 50)(&sanitize_str(&rules, &deep)));
+    assert!(exceeded(Limit::Depth, 50)(&stream_str(&rules, &deep)));
+}
+
+#[test]
+fn limit_nodes() {
+    let input = "<div>a<b>b</b>c<!-- d --></div>e";
+    let fail = limit_rules(Limits::new().max_nodes(5));
+    assert!(sanitize_str(&fail, "<div>a<b>b</b>c</div>").is_ok());
+    assert!(exceeded(Limit::Nodes, 5)(&sanitize_str(&fail, input)));
+    assert!(exceeded(Limit::Nodes, 5)(&stream_str(&fail, input)));
+
+    let truncate = limit_rules(Limits::new().max_nodes(3).on_exceed(OnLimit::Truncate));
+    assert_eq!(
+        &sanitize_str(&truncate, input).unwrap(),
+        "<div>a<b></b></div>"
+    );
+    assert_eq!(
+        &stream_str(&truncate, input).unwrap(),
+        "<div>a<b></b></div>"
+    );
+}
+
+#[test]
+fn limit_attributes() {
+    let input = "<div title=\"t\" id=\"a\">x</div>";
+    let fail = limit_rules(Limits::new().max_attributes(1));
+    assert!(sanitize_str(&fail, "<div id=\"a\">x</div>").is_ok());
+    assert!(exceeded(Limit::Attributes, 1)(&sanitize_str(&fail, input)));
+    assert!(exceeded(Limit::Attributes, 1)(&stream_str(&fail, input)));
+
+    let truncate = limit_rules(Limits::new().max_attributes(1).on_exceed(OnLimit::Truncate));
+    assert_eq!(&sanitize_str(&truncate, input).unwrap(), "<div>x</div>");
+    assert_eq!(&stream_str(&truncate, input).unwrap(), "<div>x</div>");
+    let input = "<div id=\"a\" title=\"t\">x</div>";
+    assert_eq!(
+        &sanitize_str(&truncate, input).unwrap(),
+        "<div id=\"a\">x</div>"
+    );
+}
+
+#[test]
+fn limit_validate() {
+    let rules = limit_rules(Limits::new().max_depth(1));
+    assert!(validate(&rules, "<div>a</div>").is_ok());
+    assert!(matches!(
+        validate(&rules, "<div><b>a</b></div>"),
+        Err(SanitizeError::LimitExceeded {
+            limit: Limit::Depth,
+            max: 1
+        })
+    ));
+
+    let rules = limit_rules(Limits::new().max_depth(1).on_exceed(OnLimit::Truncate));
+    assert_eq!(
+        violations(validate(&rules, "<div><b>a</b></div>")),
+        vec![Change {
+            path: "/html[1]/body[1]/div[1]/b[1]".to_owned(),
+            kind: ChangeKind::LimitExceeded {
+                limit: Limit::Depth,
+                max: 1
+            },
+        }]
+    );
+}
+
+/* encoding */
+
+#[cfg(feature = "encoding")]
+#[test]
+fn encoding_of_input() {
+    use super::encoding::EncodingOptions;
+    use super::sanitize_encoded;
+
+    let options = EncodingOptions::new();
+    // Shift_JIS declared by meta
+    let input = b"<meta charset=shift_jis><p>\x93\xfa\x96\x7b</p>";
+    assert_eq!(
+        sanitize_encoded(&BASIC, input, &options).unwrap(),
+        "<p>日本</p>".as_bytes()
+    );
+    // label passed by a caller wins over meta
+    let input = b"<meta charset=utf-8><p>Caf\xe9</p>";
+    assert_eq!(
+        sanitize_encoded(&BASIC, input, &options.input_label("latin1")).unwrap(),
+        "<p>Café</p>".as_bytes()
+    );
+    // undeclared invalid UTF-8 is windows-1252
+    assert_eq!(
+        sanitize_encoded(&BASIC, b"<p>\x93quoted\x94</p>", &options).unwrap(),
+        "<p>\u{201c}quoted\u{201d}</p>".as_bytes()
+    );
+    // BOM
+    let input = b"\xFF\xFE<\x00b\x00>\x00\xe9\x00";
+    assert_eq!(
+        sanitize_encoded(&BASIC, input, &options).unwrap(),
+        "<b>é</b>".as_bytes()
+    );
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn encoding_of_output() {
+    use super::encoding::{Encoding, EncodingOptions};
+    use super::sanitize_encoded;
+
+    let koi8 = Encoding::for_label(b"koi8-r").unwrap();
+    let options = EncodingOptions::new().input(koi8).output(koi8);
+    // characters missing in an output encoding become character references
+    let input =
+        b"<p title=\"\xf0\xd2\xc9\xd7\xc5\xd4 &#x263A;\">\xf0\xd2\xc9\xd7\xc5\xd4 &#x263A;</p>";
+    assert_eq!(
+        sanitize_encoded(&RELAXED, input, &options).unwrap(),
+        b"<p title=\"\xf0\xd2\xc9\xd7\xc5\xd4 &#9786;\">\xf0\xd2\xc9\xd7\xc5\xd4 &#9786;</p>"
+    );
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn encoding_of_strict_output() {
+    use super::encoding::{Encoding, EncodingOptions};
+    use super::sanitize_encoded;
+
+    let rules = BASIC.clone().strict(true);
+    let koi8 = Encoding::for_label(b"koi8-r").unwrap();
+    let options = EncodingOptions::new().input(koi8);
+    assert_eq!(
+        sanitize_encoded(&rules, b"<p>\xf0\xd2\xc9\xd7\xc5\xd4</p>", &options).unwrap(),
+        "<p>Привет</p>".as_bytes()
+    );
+    let latin1 = Encoding::for_label(b"latin1").unwrap();
+    let options = EncodingOptions::new().output(latin1);
+    assert_eq!(
+        sanitize_encoded(&rules, "\u{FEFF}<p>Café</p>".as_bytes(), &options).unwrap(),
+        b"<p>Caf\xe9</p>"
+    );
+}
+
+/* combining rules */
+
+#[test]
+fn rules_clone_is_independent() {
+    let rules = BASIC.clone().allow_comments(true);
+    assert_eq!(
+        &sanitize_str(&rules, "<!-- a --><b>b</b>").unwrap(),
+        "<!-- a --><b>b</b>"
+    );
+    assert_eq!(
+        &sanitize_str(&BASIC, "<!-- a --><b>b</b>").unwrap(),
+        "<b>b</b>"
+    );
+}
+
+#[test]
+fn extend_merges_elements() {
+    let rules = Rules::new()
+        .element(
+            Element::new("a")
+                .attribute("href", Pattern::any())
+                .attribute("title", Pattern::any())
+                .attribute_prefix("data-", Pattern::one_of(["1"])),
+        )
+        .extend(
+            &Rules::new().element(
+                Element::new("a")
+                    .attribute("title", Pattern::one_of(["ok"]))
+                    .attribute_wildcard("data-x", Pattern::one_of(["2"]))
+                    .mandatory_attribute("rel", "nofollow"),
+            ),
+        );
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<a href=\"/\" title=\"bad\" data-x=\"2\" data-y=\"1\">a</a>"
+        )
+        .unwrap(),
+        "<a href=\"/\" data-x=\"2\" data-y=\"1\" rel=\"nofollow\">a</a>"
+    );
+}
+
+#[test]
+fn extend_precedence() {
+    let rules = Rules::new()
+        .element(Element::new("b"))
+        .delete("i")
+        .space("u")
+        .rename("s", "b")
+        .global_attribute("title", Pattern::any())
+        .extend(
+            &Rules::new()
+                .space("b")
+                .element(Element::new("i"))
+                .delete("u")
+                .global_attribute("title", Pattern::one_of(["ok"]))
+                .allow_comments(true),
+        );
+    assert_eq!(
+        &sanitize_str(
+            &rules,
+            "<b>a</b><i title=\"no\">b</i><u>c</u><s>d</s><!--e-->"
+        )
+        .unwrap(),
+        " a <i>b</i><b>d</b><!--e-->"
+    );
+    assert!(!rules.strict);
+}
+
+#[test]
+fn extend_limits() {
+    let rules = Rules::new()
+        .limits(Limits::new().max_depth(2).max_nodes(10))
+        .extend(&Rules::new().limits(Limits::new().max_nodes(5).on_exceed(OnLimit::Truncate)));
+    assert_eq!(rules.limits.max_depth, Some(2));
+    assert_eq!(rules.limits.max_nodes, Some(5));
+    assert_eq!(rules.limits.on_exceed, OnLimit::Truncate);
+
+    let rules = rules.extend(&Rules::new());
+    assert_eq!(rules.limits.on_exceed, OnLimit::Truncate);
+}
+
+#[test]
+fn without_element() {
+    let rules = BASIC.clone().rename("center", "b").without_element("b");
+    assert!(!rules.allowed_elements.contains_key("b"));
+    assert_eq!(
+        &sanitize_str(&rules, "<b>a</b><center>b</center><i>c</i>").unwrap(),
+        "ab<i>c</i>"
+    );
+}
+
+#[test]
+fn without_attribute() {
+    let rules = RELAXED
+        .clone()
+        .class_policy(ClassPolicy::new().name("a"))
+        .without_attribute("class")
+        .without_attribute("href");
+    assert_eq!(
+        &sanitize_str(&rules, "<a href=\"/\" class=\"a\" lang=\"en\">a</a>").unwrap(),
+        "<a lang=\"en\">a</a>"
+    );
+    assert!(
+        RELAXED.allowed_elements["a"]
+            .attributes
+            .contains_key("href")
+    );
+}
+
+/* lint */
+
+#[test]
+fn presets_pass_lint() {
+    for rules in [&*BASIC, &*DEFAULT, &*RELAXED, &*RESTRICTED, &*UNTRUSTED] {
+        assert_eq!(rules.lint(), vec![]);
+    }
+}
+
+#[test]
+fn lint_categories_and_renames() {
+    let rules = Rules::new()
+        .element(Element::new("b"))
+        .space("b")
+        .rename("b", "strong")
+        .delete("script")
+        .space("script")
+        .rename("center", "div")
+        .rename("big", "small")
+        .rename("small", "b")
+        .rename("blod", "b");
+    let messages: Vec<String> = rules.lint().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "element <b> is allowed, replaced with spaces, renamed; only the first applies",
+            "element <script> is deleted, replaced with spaces; only the first applies",
+            "element <b> is renamed to <strong>, which is not allowed",
+            "element <center> is renamed to <div>, which is not allowed",
+            "element <big> is renamed to <small>, which is renamed to <b>; renames are not applied twice",
+            "<blod> is not an HTML element",
+        ]
+    );
+}
+
+#[test]
+fn lint_attributes() {
+    use super::rules::lint::Lint;
+
+    let rules = Rules::new()
+        .element(
+            Element::new("a")
+                .attribute("href", Pattern::url(UrlPolicy::new().scheme("https")))
+                .attribute("target", Pattern::one_of(["_self"]))
+                .attribute("onClick", Pattern::any())
+                .mandatory_attribute("target", "_blank")
+                .mandatory_attribute("rel", "nofollow"),
+        )
+        .element(Element::new("img").attribute("src", Pattern::any()))
+        .element(
+            Element::new("form").attribute("action", Pattern::one_of(["/search"]) | Pattern::any()),
+        )
+        .element(
+            Element::new("iframe").attribute("src", !Pattern::regex(Regex::new("^data:").unwrap())),
+        )
+        .element(Element::new("link").attribute(
+            "href",
+            AttributeRule::transform(|_| Some("/style.css".to_owned())),
+        ))
+        .element(Element::new("video").attribute_wildcard("*src", Pattern::any()))
+        .element(Element::new("my-widget").attribute_wildcard("*", Pattern::any()))
+        .global_attribute("cite", Pattern::any())
+        .global_attribute_prefix("on", Pattern::regex(Regex::new("^$").unwrap()));
+    assert_eq!(
+        rules.lint(),
+        vec![
+            Lint::MandatoryRejected {
+                element: "a".to_owned(),
+                attribute: "target".to_owned(),
+                value: "_blank".to_owned(),
+            },
+            Lint::EventHandler {
+                element: None,
+                attribute: "on*".to_owned(),
+            },
+            Lint::EventHandler {
+                element: Some("a".to_owned()),
+                attribute: "onClick".to_owned(),
+            },
+            Lint::EventHandler {
+                element: Some("my-widget".to_owned()),
+                attribute: "*".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: None,
+                attribute: "cite".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: Some("form".to_owned()),
+                attribute: "action".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: Some("iframe".to_owned()),
+                attribute: "src".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: Some("img".to_owned()),
+                attribute: "src".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: Some("my-widget".to_owned()),
+                attribute: "*".to_owned(),
+            },
+            Lint::UnrestrictedUrl {
+                element: Some("video".to_owned()),
+                attribute: "*src".to_owned(),
+            },
+        ]
+    );
+    assert_eq!(
+        rules.lint()[1].to_string(),
+        "event handler attribute on* is allowed on every element"
+    );
+    assert_eq!(
+        rules.lint()[4].to_string(),
+        "URL attribute cite on every element accepts javascript: URLs"
+    );
+}
+
+/* pattern description */
+
+#[test]
+fn pattern_display() {
+    let pattern = !Pattern::regex(Regex::new("^on").unwrap())
+        & (Pattern::one_of(["a", "b"]) | Pattern::range(..10.0));
+    assert_eq!(
+        pattern.to_string(),
+        "not regex /^on/ and (one of \"a\", \"b\" or number in (-inf, 10))"
+    );
+    assert_eq!(Pattern::range(0.5..).to_string(), "number in [0.5, inf)");
+    assert_eq!(
+        (!(Pattern::any() & Pattern::custom(|_| true))).to_string(),
+        "not (any value and custom function)"
+    );
+    assert_eq!((!Pattern::any()).to_string(), "not any value");
+}
+
+#[test]
+fn pattern_debug_and_eq() {
+    let pattern = Pattern::one_of(["a"]) | !Pattern::any();
+    assert_eq!(format!("{:?}", pattern), "Or([OneOf([\"a\"]), Not(Any)])");
+    assert_eq!(pattern, Pattern::one_of(["a"]) | !Pattern::any());
+    assert_eq!(!!Pattern::any(), Pattern::any());
+    assert_eq!(
+        Pattern::regex(Regex::new("^a$").unwrap()),
+        Pattern::regex(Regex::new("^a$").unwrap())
+    );
+    assert_ne!(Pattern::range(1.0..), Pattern::range(1.0..=2.0));
+    assert_eq!(format!("{:?}", Pattern::custom(|_| true)), "Custom(..)");
+    assert_ne!(Pattern::custom(|_| true), Pattern::custom(|_| true));
+}
+
+#[test]
+fn pattern_in_report() {
+    let rules = Rules::new().element(
+        Element::new("img")
+            .attribute("width", Pattern::range(1.0..=640.0))
+            .attribute("alt", AttributeRule::transform(|_| None)),
+    );
+    let violations = violations(validate(&rules, "<img width=\"1000\" alt=\"x\">"));
+    let messages: Vec<String> = violations.iter().map(|v| v.kind.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "attribute width=\"1000\" removed from <img>: value does not match element rule `width`: number in [1, 640]",
+            "attribute alt=\"x\" removed from <img>: value does not match element rule `alt`",
+        ]
+    );
+}
+
+/* config */
+
+#[cfg(feature = "serde")]
+const PRESET_INPUTS: &[&str] = &[
+    BASIC_HTML,
+    MALICIOUS_HTML,
+    RAW_COMMENT_HTML,
+    UNCLOSED_HTML,
+    JS_INJECTION_HTML_1,
+    JS_INJECTION_HTML_5,
+    "<p title=\"a\">x<img src=\"a.png\" alt=\"&quot;&lt;\">y</p><ul><li>1</li><li>2</li></ul>",
+    "hello <style media=\"print\"><!-- comment-->* {color: /*white*/#ffffff;}</style> world",
+    "<blockquote cite=\"javascript:alert(1)\"><q cite=\"/quote\">q</q></blockquote>",
+    "<a href=\"https://example.com/\" rel=\"me\">a</a><a href=\"vbscript:x\">b</a>",
+];
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_expresses_basic() {
+    let rules: Rules = serde_json::from_str(
+        r#"{
+            "elements": {
+                "a": { "attributes": { "href": { "url": {
+                    "schemes": ["ftp", "http", "https", "mailto"],
+                    "allow_relative": true,
+                    "allow_protocol_relative": true
+                } } } },
+                "abbr": { "attributes": { "title": "any" } },
+                "b": {}, "br": {}, "cite": {}, "code": {}, "dd": {}, "dl": {}, "dt": {},
+                "em": {}, "i": {}, "kbd": {}, "li": {}, "mark": {}, "ol": {}, "p": {},
+                "pre": {}, "s": {}, "samp": {}, "small": {}, "strike": {}, "strong": {},
+                "sub": {}, "sup": {}, "u": {}, "ul": {}, "var": {},
+                "blockquote": { "attributes": { "cite": { "url": {
+                    "schemes": ["http", "https"],
+                    "allow_relative": true,
+                    "allow_protocol_relative": true
+                } } } },
+                "q": { "attributes": { "cite": { "url": {
+                    "schemes": ["http", "https"],
+                    "allow_relative": true,
+                    "allow_protocol_relative": true
+                } } } },
+                "dfn": { "attributes": { "title": "any" } },
+                "time": { "attributes": { "datetime": "any", "pubdate": "any" } },
+                "style": { "attributes": { "media": "any" } }
+            },
+            "space": [
+                "address", "article", "aside", "div", "footer", "h1", "h2", "h3", "h4",
+                "h5", "h6", "header", "hgroup", "hr", "nav", "section"
+            ]
+        }"#,
+    )
+    .unwrap();
+    for input in PRESET_INPUTS {
+        assert_eq!(
+            sanitize_str(&rules, input).unwrap(),
+            sanitize_str(&BASIC, input).unwrap()
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_expresses_untrusted() {
+    let rules: Rules = toml::from_str(
+        r#"
+        space = [
+            "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt",
+            "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hgroup", "hr", "li",
+            "nav", "ol", "p", "pre", "section", "ul",
+        ]
+        delete = ["noscript", "script", "style"]
+
+        [link_policy]
+        rel = ["nofollow", "ugc", "noopener", "noreferrer"]
+        allowed_rel = [
+            "author", "bookmark", "external", "help", "license", "me", "next", "prev",
+            "sponsored", "tag",
+        ]
+        target_blank = true
+
+        [elements]
+        b = {}
+        em = {}
+        i = {}
+        strong = {}
+        u = {}
+
+        [elements.a.attributes.href.url]
+        schemes = ["ftp", "http", "https", "mailto"]
+        allow_relative = true
+        allow_protocol_relative = true
+        "#,
+    )
+    .unwrap();
+    for input in PRESET_INPUTS {
+        assert_eq!(
+            sanitize_str(&rules, input).unwrap(),
+            sanitize_str(&UNTRUSTED, input).unwrap()
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_patterns() {
+    use super::rules::config::PatternConfig;
+
+    let pattern = |json: &str| {
+        let config: PatternConfig = serde_json::from_str(json).unwrap();
+        Pattern::try_from(&config).unwrap()
+    };
+    assert!(pattern(r#""any""#).matches("anything"));
+    assert!(pattern(r#"{ "regex": "^[a-z]+$" }"#).matches("abc"));
+    assert!(!pattern(r#"{ "regex": "^[a-z]+$" }"#).matches("a1"));
+    assert!(pattern(r#"{ "one_of": ["ltr", "rtl"] }"#).matches("RTL"));
+    assert!(pattern(r#"{ "range": { "min": 1 } }"#).matches("100000"));
+    assert!(!pattern(r#"{ "range": { "min": 1, "max": 9 } }"#).matches("10"));
+
+    let combined = pattern(
+        r#"{ "and": [
+            { "or": [{ "one_of": ["auto"] }, { "range": { "min": 0, "max": 100 } }] },
+            { "not": { "one_of": ["13"] } }
+        ] }"#,
+    );
+    assert!(combined.matches("auto"));
+    assert!(combined.matches("42"));
+    assert!(!combined.matches("13"));
+    assert!(!combined.matches("101"));
+    assert!(!pattern(r#"{ "or": [] }"#).matches(""));
+    assert!(pattern(r#"{ "and": [] }"#).matches(""));
+    assert_eq!(
+        pattern(r#"{ "and": [{ "regex": "a" }, { "regex": "b" }] }"#).to_string(),
+        "regex /a/ and regex /b/"
+    );
+    assert_eq!(
+        pattern(r#"{ "or": [{ "regex": "a" }, { "regex": "b" }] }"#).to_string(),
+        "regex /a/ or regex /b/"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_errors() {
+    use super::rules::config::{ConfigError, RulesConfig};
+
+    let config: RulesConfig = serde_json::from_str(
+        r#"{ "elements": { "b": { "attributes": { "title": { "regex": "(" } } } } }"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        Rules::try_from(config),
+        Err(ConfigError::Regex(_))
+    ));
+
+    let config: RulesConfig = serde_json::from_str(r#"{ "base_url": "/relative" }"#).unwrap();
+    assert!(matches!(
+        Rules::try_from(config),
+        Err(ConfigError::BaseUrl(_))
+    ));
+
+    let error = serde_json::from_str::<Rules>(r#"{ "element": {} }"#)
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("unknown field `element`"));
+    let error =
+        serde_json::from_str::<Rules>(r#"{ "global_attributes": { "id": { "regex": "[" } } }"#)
+            .err()
+            .unwrap();
+    assert!(error.to_string().starts_with("invalid regular expression"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_round_trip() {
+    use super::rules::config::RulesConfig;
+
+    let json = r#"{
+        "strict": true,
+        "elements": {
+            "p": {
+                "style": { "properties": { "color": "any" }, "urls": { "schemes": ["https"] } },
+                "class": { "names": ["lead"], "patterns": ["^col-[0-9]+$"] },
+                "wildcard_attributes": [["data-*", "any"]]
+            }
+        },
+        "rename": { "center": "p" },
+        "base_url": "https://example.com/",
+        "limits": { "max_depth": 10, "on_exceed": "flatten" },
+        "link_policy": { "rel": ["nofollow"], "allowed_rel": ["me"], "target_blank": true }
+    }"#;
+    let config: RulesConfig = serde_json::from_str(json).unwrap();
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RulesConfig>(&serialized).unwrap(),
+        config
+    );
+
+    let rules = Rules::try_from(config).unwrap();
+    let input = "<p class=\"lead big\" style=\"color: red; margin: 0\" data-id=\"1\">a</p>\
+        <center>b</center>";
+    assert!(sanitize_str(&rules, input).is_err());
+    assert_eq!(
+        sanitize_str(&rules.strict(false), input).unwrap(),
+        "<p class=\"lead\" style=\"color: red\" data-id=\"1\">a</p><p>b</p>"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_serializes_presets() {
+    use super::rules::config::RulesConfig;
+
+    for preset in [&*BASIC, &*DEFAULT, &*RELAXED, &*RESTRICTED, &*UNTRUSTED] {
+        let json = serde_json::to_string(preset).unwrap();
+        let rules: Rules = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            RulesConfig::try_from(&rules).unwrap(),
+            RulesConfig::try_from(preset).unwrap()
+        );
+        for input in PRESET_INPUTS {
+            assert_eq!(
+                sanitize_str(&rules, input).unwrap(),
+                sanitize_str(preset, input).unwrap()
+            );
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_serialization_errors() {
+    let rules =
+        Rules::new().element(Element::new("b").attribute("title", Pattern::custom(|_| true)));
+    let error = serde_json::to_string(&rules).err().unwrap();
+    assert_eq!(error.to_string(), "custom pattern can not be described");
+
+    let rules =
+        Rules::new().element(Element::new("img").attribute("width", Pattern::range(0.0..10.0)));
+    assert!(serde_json::to_string(&rules).is_err());
+
+    let rules = Rules::new().transformer(|_| None);
+    assert!(serde_json::to_string(&rules).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_mandatory_modes() {
+    let rules: Rules = serde_json::from_str(
+        r#"{ "elements": { "a": {
+            "attributes": { "rel": "any", "title": "any" },
+            "mandatory_attributes": {
+                "rel": { "value": "nofollow", "mode": "append_tokens" },
+                "title": "link"
+            }
+        } } }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        &sanitize_str(&rules, "<a rel=\"me\" title=\"home\">a</a>").unwrap(),
+        "<a rel=\"me nofollow\" title=\"link\">a</a>"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_rename_keeping_attributes() {
+    let rules: Rules = serde_json::from_str(
+        r#"{
+            "elements": { "strong": { "attributes": { "title": "any" } } },
+            "rename": {
+                "b": { "to": "strong", "keep_attributes": true },
+                "i": "strong"
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        &sanitize_str(&rules, "<b title=\"a\">a</b><i title=\"b\">b</i>").unwrap(),
+        "<strong title=\"a\">a</strong><strong>b</strong>"
+    );
+}
+
+/* deep nesting */
+
+#[test]
+fn deep_nesting() {
+    // elements which html5ever nests without scanning the whole stack of open elements
+    let levels = 50_000;
+    let input = format!(
+        "{}x{}",
+        "<b><span>".repeat(levels),
+        "</span></b>".repeat(levels)
+    );
+    for rules in [&*BASIC, &*DEFAULT, &*RELAXED, &*RESTRICTED, &*UNTRUSTED] {
+        let result = sanitize_str(rules, &input).unwrap();
+        assert!(result.contains('x'));
+        assert_eq!(stream_str(rules, &input).unwrap(), result);
+    }
+}
+
+#[test]
+fn deep_nesting_space() {
+    let rules = Rules::new().element(Element::new("b")).space("span");
+    let levels = 50_000;
+    let input = format!(
+        "{}x{}",
+        "<b><span>".repeat(levels),
+        "</span></b>".repeat(levels)
+    );
+    let expected = format!("{}x{}", "<b> ".repeat(levels), " </b>".repeat(levels));
+    assert_eq!(sanitize_str(&rules, &input).unwrap(), expected);
+}